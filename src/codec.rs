@@ -0,0 +1,1005 @@
+use crate::config::Config;
+use crate::error::Error;
+use crate::image::{dct, dwt, keep_largest, share, split};
+use crate::image::{Channel, ColorTransform, Image, Plane, Selection, Subsampling, Wavelet};
+use crate::quantization::Quantization;
+use crate::rd;
+
+/*Codecs*/
+
+// a transform coding of an image: encode keeps the coefficient budget of the image in the
+// codec's coefficient representation, decode reconstructs the pixels from it, the parameters
+// of the transform belong to the codec
+pub trait Codec {
+    // dct, dwt, ...
+    fn name(&self) -> &'static str;
+
+    // the name with the parameters that change the reconstruction but are not in the window
+    // title, used in file names, tables and metrics, e.g. dwt_haar
+    fn tag(&self) -> String {
+        self.name().to_string()
+    }
+
+    // the parameters the image is coded with, e.g. dct 8x8 q50
+    fn describe(&self, image: &Image) -> String;
+
+    fn encode(&self, image: &Image) -> Result<Coefficients, Error>;
+
+    fn decode(&self, coefficients: &Coefficients) -> Result<Image, Error>;
+
+    // the alternatives the window offers in a combo box, e.g. the wavelets, none by default
+    fn choices(&self) -> Vec<&'static str> {
+        Vec::new()
+    }
+
+    fn choice(&self) -> Option<&'static str> {
+        None
+    }
+
+    // the same codec with another of its choices, None when name is not one of them
+    fn with_choice(&self, _name: &str) -> Option<Box<dyn Codec>> {
+        None
+    }
+
+    // the coefficients of image encoded with coefficient coefficients per channel and the
+    // reconstruction decoded from them
+    fn reconstruct(
+        &self,
+        image: &Image,
+        coefficient: usize,
+    ) -> Result<(Coefficients, Image), Error> {
+        let mut image = image.clone();
+        image.set_coefficient(coefficient);
+        let coefficients = self.encode(&image)?;
+        let reconstruction = self.decode(&coefficients)?;
+        Ok((coefficients, reconstruction))
+    }
+}
+
+// every codec compared by the window, the headless run and the rate-distortion tables, in the
+// order of the columns, with the parameters of the configuration
+pub fn all(config: &Config) -> Result<Vec<Box<dyn Codec>>, Error> {
+    let mut dct = Dct::new(config.blocksize_x, config.blocksize_y);
+    dct.set_quantization(config.quantization()?);
+    let mut dwt = Dwt::new(config.wavelet, config.levels);
+    dwt.set_tile_size(config.tile_size);
+    Ok(vec![Box::new(dct), Box::new(dwt)])
+}
+
+/*Coefficients*/
+
+// what a codec keeps of an image, one plane per component indexed like Channel::to_number,
+// with the settings of the image that decoding needs to get the pixels back and the codec and
+// parameters that encoded it
+#[derive(Clone)]
+pub struct Coefficients {
+    codec: String,
+    parameters: String,
+    image: Image,
+    planes: Vec<Plane>,
+}
+
+impl Coefficients {
+    fn new<C: Codec + std::fmt::Debug>(codec: &C, image: &Image, planes: Vec<Plane>) -> Self {
+        Self {
+            codec: codec.tag(),
+            parameters: format!("{:?}", codec),
+            image: image.without_pixels(),
+            planes,
+        }
+    }
+
+    // the tag of the codec that encoded the coefficients, e.g. dwt_haar
+    pub fn get_codec(&self) -> &str {
+        &self.codec
+    }
+
+    // only the codec and parameters that encoded the coefficients decode them
+    fn check<C: Codec + std::fmt::Debug>(&self, codec: &C) -> Result<(), Error> {
+        if self.codec != codec.tag() {
+            return Err(Error::CodecMismatch(format!(
+                "{} coefficients cannot be decoded by {}",
+                self.codec,
+                codec.tag()
+            )));
+        }
+        if self.parameters != format!("{:?}", codec) {
+            return Err(Error::CodecMismatch(format!(
+                "{} coefficients were encoded with other parameters than {:?}",
+                self.codec, codec
+            )));
+        }
+        Ok(())
+    }
+
+    pub fn get_size(&self) -> (usize, usize) {
+        self.image.get_size()
+    }
+
+    pub fn get_coefficient(&self) -> usize {
+        self.image.get_coefficient()
+    }
+
+    // nonzero coefficients of every channel, indexed like Image::channel_names
+    pub fn kept_coefficients(&self) -> [usize; 3] {
+        let mut kept = [0; 3];
+        for (k, plane) in kept.iter_mut().zip(&self.planes) {
+            *k = plane
+                .samples
+                .iter()
+                .flatten()
+                .filter(|f| **f != 0.0)
+                .count();
+        }
+        kept
+    }
+
+    // estimated size of the kept coefficients of every channel in bits
+    pub fn estimated_bits(&self) -> f64 {
+        self.planes
+            .iter()
+            .map(|plane| {
+                let coefficients: Vec<f64> = plane.samples.iter().flatten().cloned().collect();
+                rd::estimated_bits(&coefficients)
+            })
+            .sum()
+    }
+
+    // the image the planes decode to
+    fn to_image(&self, planes: &[Plane]) -> Image {
+        let mut image = self.image.clone();
+        image.set_planes(planes);
+        image
+    }
+}
+
+/*DCT*/
+
+// block based DCT as in JPEG
+#[derive(Clone, Debug)]
+pub struct Dct {
+    blocksize_x: usize,
+    blocksize_y: usize,
+    quantization: Option<Quantization>,
+}
+
+impl Default for Dct {
+    fn default() -> Self {
+        Self::new(8, 8)
+    }
+}
+
+impl Dct {
+    pub fn new(blocksize_x: usize, blocksize_y: usize) -> Self {
+        Self {
+            blocksize_x,
+            blocksize_y,
+            quantization: None,
+        }
+    }
+
+    pub fn get_blocksize(&self) -> (usize, usize) {
+        (self.blocksize_x, self.blocksize_y)
+    }
+
+    pub fn set_blocksize(&mut self, blocksize_x: usize, blocksize_y: usize) -> &mut Self {
+        self.blocksize_x = blocksize_x;
+        self.blocksize_y = blocksize_y;
+        self
+    }
+
+    pub fn get_quantization(&self) -> Option<&Quantization> {
+        self.quantization.as_ref()
+    }
+
+    // quantize every block with the scaled tables, None keeps the coefficients unrounded
+    pub fn set_quantization(&mut self, quantization: Option<Quantization>) -> &mut Self {
+        self.quantization = quantization;
+        self
+    }
+
+    fn check_blocksize(&self) -> Result<(), Error> {
+        if self.blocksize_x == 0 || self.blocksize_y == 0 {
+            return Err(Error::InvalidDimensions(format!(
+                "a {}x{} DCT block has no samples",
+                self.blocksize_x, self.blocksize_y
+            )));
+        }
+        Ok(())
+    }
+
+    // (x_start, x_length, y_start, y_length) of every block of a plane, the last row and
+    // column of blocks are cut short when the plane is not a multiple of the block size
+    fn blocks(&self, image: &Image, c: &Channel) -> Vec<(usize, usize, usize, usize)> {
+        let (width, height) = image.plane_size(c);
+        split(width, height, self.blocksize_x, self.blocksize_y)
+    }
+
+    // zigzag coefficients kept in every block of a plane so that they add up to exactly its
+    // budget, every block gets an even share and the rest goes to the blocks whose next
+    // coefficient carries the most energy
+    fn numbers(
+        &self,
+        image: &Image,
+        frequencies: &Plane,
+        c: &Channel,
+        number: usize,
+    ) -> Vec<usize> {
+        let frequencies = &frequencies.samples;
+        let blocks = self.blocks(image, c);
+        let areas: Vec<usize> = blocks.iter().map(|&(_, w, _, h)| w * h).collect();
+        if blocks.is_empty() {
+            return vec![];
+        }
+
+        let base = number / blocks.len();
+        let mut numbers: Vec<usize> = areas.iter().map(|&a| std::cmp::min(base, a)).collect();
+        let total = std::cmp::min(number, areas.iter().sum());
+        let mut rest = total - numbers.iter().sum::<usize>();
+
+        while rest > 0 {
+            let mut candidates: Vec<(usize, f64)> = blocks
+                .iter()
+                .enumerate()
+                .filter(|&(i, _)| numbers[i] < areas[i])
+                .map(|(i, &(x_start, x_length, y_start, y_length))| {
+                    let energy = dct::zigzag_energy(
+                        frequencies,
+                        numbers[i],
+                        x_start,
+                        x_length,
+                        y_start,
+                        y_length,
+                    );
+                    (i, energy)
+                })
+                .collect();
+            candidates.sort_by(|a, b| b.1.partial_cmp(&a.1).unwrap());
+
+            for (i, _) in candidates.into_iter().take(rest) {
+                numbers[i] += 1;
+                rest -= 1;
+            }
+        }
+        numbers
+    }
+}
+
+impl Codec for Dct {
+    fn name(&self) -> &'static str {
+        "dct"
+    }
+
+    fn describe(&self, image: &Image) -> String {
+        let mut label = format!("dct {}x{}", self.blocksize_x, self.blocksize_y);
+        if let Some(quantization) = &self.quantization {
+            label += &format!(" q{}", quantization.quality);
+        }
+        label + &common_parameters(image)
+    }
+
+    fn encode(&self, image: &Image) -> Result<Coefficients, Error> {
+        self.check_blocksize()?;
+        let planes = image.planes()?;
+        let mut frequencies: Vec<Plane> = planes
+            .iter()
+            .map(|plane| Plane::new(plane.width, plane.height))
+            .collect();
+
+        // every block is transformed in full, the budget is applied afterwards
+        let mut transforms = dct::Transforms::new();
+        for c in image.get_color().channels() {
+            let n = c.to_number();
+            for (x_start, x_length, y_start, y_length) in self.blocks(image, c) {
+                // R, G, B and Y use the luminance table, Cb and Cr the chrominance one
+                let steps = self.quantization.as_ref().map(|quantization| {
+                    if c.is_chroma() {
+                        quantization.chrominance.steps(x_length, y_length)
+                    } else {
+                        quantization.luminance.steps(x_length, y_length)
+                    }
+                });
+
+                dct::dct_encode_block(
+                    transforms.get(x_length, y_length),
+                    &planes[n].samples,
+                    &mut frequencies[n].samples,
+                    usize::MAX,
+                    steps.as_deref(),
+                    x_start,
+                    y_start,
+                );
+            }
+        }
+
+        let numbers = image.plane_numbers();
+        if image.get_selection() == Selection::Zigzag {
+            for c in image.get_color().channels() {
+                let n = c.to_number();
+                let block_numbers = self.numbers(image, &frequencies[n], c, numbers[n]);
+                for ((x_start, x_length, y_start, y_length), number) in
+                    self.blocks(image, c).into_iter().zip(block_numbers)
+                {
+                    dct::truncate_block(
+                        &mut frequencies[n].samples,
+                        number,
+                        x_start,
+                        x_length,
+                        y_start,
+                        y_length,
+                    );
+                }
+            }
+        } else {
+            keep_largest(&mut frequencies, &numbers, image.get_selection());
+        }
+        Ok(Coefficients::new(self, image, frequencies))
+    }
+
+    fn decode(&self, coefficients: &Coefficients) -> Result<Image, Error> {
+        self.check_blocksize()?;
+        coefficients.check(self)?;
+        let image = &coefficients.image;
+        let mut planes: Vec<Plane> = coefficients
+            .planes
+            .iter()
+            .map(|plane| Plane::new(plane.width, plane.height))
+            .collect();
+
+        let mut transforms = dct::Transforms::new();
+        for c in image.get_color().channels() {
+            let n = c.to_number();
+            for (x_start, x_length, y_start, y_length) in self.blocks(image, c) {
+                dct::dct_decode_block(
+                    transforms.get(x_length, y_length),
+                    &mut planes[n].samples,
+                    &coefficients.planes[n].samples,
+                    self.quantization.is_some(),
+                    x_start,
+                    y_start,
+                );
+            }
+        }
+        Ok(coefficients.to_image(&planes))
+    }
+}
+
+/*DWT*/
+
+// multi-level DWT as in JPEG2000
+#[derive(Clone, Debug)]
+pub struct Dwt {
+    wavelet: Wavelet,
+    levels: usize,
+    tile_size: Option<(usize, usize)>,
+}
+
+impl Default for Dwt {
+    fn default() -> Self {
+        Self::new(Wavelet::Haar, 5)
+    }
+}
+
+impl Dwt {
+    pub fn new(wavelet: Wavelet, levels: usize) -> Self {
+        Self {
+            wavelet,
+            levels,
+            tile_size: None,
+        }
+    }
+
+    pub fn get_wavelet(&self) -> Wavelet {
+        self.wavelet
+    }
+
+    pub fn set_wavelet(&mut self, wavelet: Wavelet) -> &mut Self {
+        self.wavelet = wavelet;
+        self
+    }
+
+    pub fn get_levels(&self) -> usize {
+        self.levels
+    }
+
+    // number of decomposition levels, capped by what the image size allows
+    pub fn set_levels(&mut self, levels: usize) -> &mut Self {
+        self.levels = levels;
+        self
+    }
+
+    pub fn get_tile_size(&self) -> Option<(usize, usize)> {
+        self.tile_size
+    }
+
+    // transform and truncate every tile_x x tile_y tile independently, None for a single tile
+    // covering the whole image
+    pub fn set_tile_size(&mut self, tile_size: Option<(usize, usize)>) -> &mut Self {
+        self.tile_size = tile_size;
+        self
+    }
+
+    fn check_tile_size(&self) -> Result<(), Error> {
+        match self.tile_size {
+            Some((tile_x, tile_y)) if tile_x == 0 || tile_y == 0 => Err(Error::InvalidDimensions(
+                format!("a {}x{} DWT tile has no samples", tile_x, tile_y),
+            )),
+            _ => Ok(()),
+        }
+    }
+
+    // the tiles of a plane in the same layout as the DCT blocks, a single tile covers the whole
+    // plane when tiling is off, tiles of subsampled planes shrink with them
+    fn tiles(&self, image: &Image, c: &Channel) -> Vec<(usize, usize, usize, usize)> {
+        let (width, height) = image.plane_size(c);
+        match self.tile_size {
+            Some((tile_x, tile_y)) => {
+                let (x_factor, y_factor) = image.factors(c);
+                split(
+                    width,
+                    height,
+                    tile_x.div_ceil(x_factor),
+                    tile_y.div_ceil(y_factor),
+                )
+            }
+            None => vec![(0, width, 0, height)],
+        }
+    }
+}
+
+impl Codec for Dwt {
+    fn name(&self) -> &'static str {
+        "dwt"
+    }
+
+    fn tag(&self) -> String {
+        format!("dwt_{}", self.wavelet.name())
+    }
+
+    fn describe(&self, image: &Image) -> String {
+        let mut label = format!("dwt {} levels", self.levels);
+        if let Some((tile_x, tile_y)) = self.tile_size {
+            label += &format!(" {}x{} tiles", tile_x, tile_y);
+        }
+        label + &common_parameters(image)
+    }
+
+    fn encode(&self, image: &Image) -> Result<Coefficients, Error> {
+        self.check_tile_size()?;
+        let mut frequencies = image.planes()?;
+        let numbers = image.plane_numbers();
+
+        for c in image.get_color().channels() {
+            let n = c.to_number();
+            let tiles = self.tiles(image, c);
+            let areas: Vec<usize> = tiles.iter().map(|&(_, w, _, h)| w * h).collect();
+            let tile_numbers = match image.get_selection() {
+                Selection::Zigzag => share(numbers[n], &areas),
+                _ => areas.clone(),
+            };
+
+            for (&tile, &number) in tiles.iter().zip(&tile_numbers) {
+                dwt::dwt_encode_block(
+                    &mut frequencies[n].samples,
+                    number,
+                    self.wavelet,
+                    self.levels,
+                    tile,
+                );
+            }
+        }
+
+        keep_largest(&mut frequencies, &numbers, image.get_selection());
+        Ok(Coefficients::new(self, image, frequencies))
+    }
+
+    fn decode(&self, coefficients: &Coefficients) -> Result<Image, Error> {
+        self.check_tile_size()?;
+        coefficients.check(self)?;
+        let image = &coefficients.image;
+        let mut planes = coefficients.planes.clone();
+        for c in image.get_color().channels() {
+            for tile in self.tiles(image, c) {
+                dwt::dwt_decode_block(
+                    &mut planes[c.to_number()].samples,
+                    self.wavelet,
+                    self.levels,
+                    tile,
+                );
+            }
+        }
+        Ok(coefficients.to_image(&planes))
+    }
+
+    fn choices(&self) -> Vec<&'static str> {
+        Wavelet::iterator().map(|w| w.name()).collect()
+    }
+
+    fn choice(&self) -> Option<&'static str> {
+        Some(self.wavelet.name())
+    }
+
+    fn with_choice(&self, name: &str) -> Option<Box<dyn Codec>> {
+        let mut dwt = self.clone();
+        dwt.set_wavelet(Wavelet::from_name(name)?);
+        Some(Box::new(dwt))
+    }
+}
+
+// the subsampling and selection when they differ from the default
+fn common_parameters(image: &Image) -> String {
+    let mut label = String::new();
+    // RGB has no chroma planes to subsample
+    if image.get_subsampling() != Subsampling::S444 && image.get_color() != ColorTransform::Rgb {
+        label += &format!(" {}", image.get_subsampling().name());
+    }
+    if image.get_selection() != Selection::Zigzag {
+        label += &format!(" {}", image.get_selection().name());
+    }
+    label
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::image::tests::test_data;
+    use crate::image::Filter;
+    use crate::quantization::QuantizationTable;
+
+    fn round_trip(codec: &dyn Codec, image: &Image) -> Vec<u8> {
+        let coefficients = codec.encode(image).unwrap();
+        codec.decode(&coefficients).unwrap().to_1d_vec().unwrap()
+    }
+
+    #[test]
+    fn reconstruct_keeps_the_budget() {
+        let data = test_data(16, 16);
+        let image = Image::new_from_rgb(16, 16, 256, &data).unwrap();
+        let pixels = image.to_1d_vec().unwrap();
+        for codec in &[&Dct::default() as &dyn Codec, &Dwt::default()] {
+            // every coefficient brings the pixels back
+            assert_eq!(round_trip(*codec, &image), pixels);
+
+            let (coefficients, reconstruction) = codec.reconstruct(&image, 10).unwrap();
+            assert_eq!(coefficients.get_coefficient(), 10);
+            assert_eq!(reconstruction.get_coefficient(), 10);
+            assert_eq!(coefficients.get_size(), (16, 16));
+            assert!(coefficients.kept_coefficients().iter().all(|&k| k <= 10));
+            assert!(coefficients.estimated_bits() > 0.0);
+            assert_ne!(reconstruction.to_1d_vec().unwrap(), pixels);
+        }
+
+        // the coefficients decode to the settings of the encoded image
+        let mut ycbcr = image.clone();
+        ycbcr.set_color(ColorTransform::Ict);
+        let coefficients = Dct::default().encode(&ycbcr).unwrap();
+        let decoded = Dct::default().decode(&coefficients).unwrap();
+        assert_eq!(decoded.channel_names(), ["Y", "Cb", "Cr"]);
+    }
+
+    #[test]
+    fn errors_instead_of_panics() {
+        assert!(Dct::default().encode(&Image::new()).is_err());
+        assert!(Dwt::default().encode(&Image::new()).is_err());
+
+        // zero block and tile sizes cannot split the image
+        let data = test_data(16, 16);
+        let image = Image::new_from_rgb(16, 16, 10, &data).unwrap();
+        let coefficients = Dct::default().encode(&image).unwrap();
+        for &(x, y) in &[(0, 0), (8, 0)] {
+            let dct = Dct::new(x, y);
+            assert!(matches!(
+                dct.encode(&image),
+                Err(Error::InvalidDimensions(_))
+            ));
+            assert!(matches!(
+                dct.decode(&coefficients),
+                Err(Error::InvalidDimensions(_))
+            ));
+        }
+        let mut dwt = Dwt::default();
+        dwt.set_tile_size(Some((0, 0)));
+        assert!(matches!(
+            dwt.encode(&image),
+            Err(Error::InvalidDimensions(_))
+        ));
+        assert!(matches!(
+            dwt.decode(&coefficients),
+            Err(Error::InvalidDimensions(_))
+        ));
+    }
+
+    #[test]
+    fn decode_with_the_encoding_parameters() {
+        let data = test_data(16, 16);
+        let image = Image::new_from_rgb(16, 16, 10, &data).unwrap();
+        let dct = Dct::default().encode(&image).unwrap();
+        let dwt = Dwt::default().encode(&image).unwrap();
+        assert_eq!((dct.get_codec(), dwt.get_codec()), ("dct", "dwt_haar"));
+
+        let mut quantized = Dct::default();
+        quantized.set_quantization(Some(Quantization::jpeg(50)));
+        let cdf53 = Dwt::new(Wavelet::Cdf53, 5);
+        let mut three_levels = Dwt::default();
+        three_levels.set_levels(3);
+        for (codec, coefficients) in [
+            (&Dwt::default() as &dyn Codec, &dct),
+            (&Dct::default(), &dwt),
+            (&quantized, &dct),
+            (&Dct::new(4, 4), &dct),
+            (&cdf53, &dwt),
+            (&three_levels, &dwt),
+        ]
+        .iter()
+        {
+            assert!(matches!(
+                codec.decode(coefficients),
+                Err(Error::CodecMismatch(_))
+            ));
+        }
+        assert!(Dct::default().decode(&dct).is_ok());
+        assert!(Dwt::default().decode(&dwt).is_ok());
+    }
+
+    #[test]
+    fn names_and_parameters() {
+        let data = test_data(16, 16);
+        let mut image = Image::new_from_rgb(16, 16, 16, &data).unwrap();
+        let mut dct = Dct::new(16, 8);
+        dct.set_quantization(Some(Quantization::jpeg(75)));
+        let mut dwt = Dwt::new(Wavelet::Cdf97, 3);
+        dwt.set_tile_size(Some((8, 8)));
+
+        assert_eq!(dct.tag(), "dct");
+        assert_eq!(dwt.tag(), "dwt_cdf97");
+        assert_eq!(Dct::default().describe(&image), "dct 8x8");
+        image.set_selection(Selection::Largest);
+        assert_eq!(dct.describe(&image), "dct 16x8 q75 largest");
+        assert_eq!(dwt.describe(&image), "dwt 3 levels 8x8 tiles largest");
+        // subsampling only shows once there are chroma planes to subsample
+        image.set_subsampling(Subsampling::S420);
+        assert_eq!(Dct::default().describe(&image), "dct 8x8 largest");
+        image.set_color(ColorTransform::Ict);
+        assert_eq!(Dct::default().describe(&image), "dct 8x8 4:2:0 largest");
+
+        // only the DWT offers a choice, of the wavelet
+        assert!(dct.choices().is_empty() && dct.with_choice("haar").is_none());
+        assert_eq!(dwt.choices().len(), Wavelet::iterator().len());
+        assert_eq!(dwt.choice(), Some("cdf97"));
+        let haar = dwt.with_choice("haar").unwrap();
+        assert_eq!(
+            (haar.tag(), haar.describe(&image)),
+            ("dwt_haar".to_string(), dwt.describe(&image))
+        );
+        assert!(dwt.with_choice("daubechies").is_none());
+    }
+
+    #[test]
+    fn codecs_of_the_configuration() {
+        let args: Vec<String> = ["a.rgb", "64", "--blocksize", "16x8", "--quality", "50"]
+            .iter()
+            .chain(&["--wavelet", "cdf53", "--levels", "2", "--tile", "32"])
+            .map(|a| a.to_string())
+            .collect();
+        let config = Config::from_args(&args).unwrap();
+        let image = Image::new_from_rgb(1, 1, 1, &[0, 0, 0]).unwrap();
+        let codecs = all(&config).unwrap();
+        let describe: Vec<(String, String)> = codecs
+            .iter()
+            .map(|codec| (codec.tag(), codec.describe(&image)))
+            .collect();
+        assert_eq!(
+            describe,
+            vec![
+                ("dct".to_string(), "dct 16x8 q50".to_string()),
+                (
+                    "dwt_cdf53".to_string(),
+                    "dwt 2 levels 32x32 tiles".to_string()
+                )
+            ]
+        );
+    }
+
+    #[test]
+    fn level_shift_only_when_quantizing() {
+        let data = test_data(16, 16);
+        let image = Image::new_from_rgb(16, 16, 0, &data).unwrap();
+        // without coefficients both transforms decode to black
+        let black = round_trip(&Dct::default(), &image);
+        assert!(black.iter().all(|&v| v == 0));
+        assert_eq!(black, round_trip(&Dwt::default(), &image));
+
+        // quantized blocks without coefficients decode to the level shift
+        let mut dct = Dct::default();
+        dct.set_quantization(Some(Quantization::jpeg(50)));
+        assert!(round_trip(&dct, &image).iter().all(|&v| v == 128));
+    }
+
+    #[test]
+    fn dct_artifacts() {
+        let (width, height) = (32, 32);
+        let data: Vec<u8> = (0..3 * width * height)
+            .map(|i| (i % width * 3 + i / width % height * 2 + i / (width * height) * 30) as u8)
+            .collect();
+        let original = Image::new_from_rgb(width, height, 64, &data).unwrap();
+        assert!(original.blockiness(8, 8).unwrap().overall.abs() < 1e-12);
+
+        // one coefficient per block leaves flat blocks with steps at the block grid
+        let (_, image_dct) = Dct::default().reconstruct(&original, 16).unwrap();
+        assert!(image_dct.blockiness(8, 8).unwrap().overall > 5.0);
+    }
+
+    #[test]
+    fn dct_round_trip_partial_blocks() {
+        let (width, height) = (20, 13);
+        let data = test_data(width, height);
+        let original = Image::new_from_rgb(width, height, 1 << 20, &data).unwrap();
+        for &(blocksize_x, blocksize_y) in &[(8, 8), (4, 4), (16, 8), (32, 32)] {
+            let dct = Dct::new(blocksize_x, blocksize_y);
+            assert_eq!(round_trip(&dct, &original), original.to_1d_vec().unwrap());
+        }
+    }
+
+    #[test]
+    fn dwt_round_trip_every_wavelet() {
+        let (width, height) = (32, 32);
+        let data = test_data(width, height);
+        let original = Image::new_from_rgb(width, height, width * height, &data).unwrap();
+        for wavelet in Wavelet::iterator() {
+            assert_eq!(
+                round_trip(&Dwt::new(*wavelet, 5), &original),
+                original.to_1d_vec().unwrap(),
+                "{}",
+                wavelet.name()
+            );
+        }
+    }
+
+    #[test]
+    fn dwt_round_trip_any_size() {
+        for &(width, height) in &[(513, 300), (1, 1), (1, 9), (7, 1), (33, 17)] {
+            let data = test_data(width, height);
+            let original = Image::new_from_rgb(width, height, width * height, &data).unwrap();
+            for wavelet in Wavelet::iterator() {
+                assert!(
+                    round_trip(&Dwt::new(*wavelet, 5), &original) == original.to_1d_vec().unwrap(),
+                    "{} {}x{}",
+                    wavelet.name(),
+                    width,
+                    height
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn dwt_round_trip_any_level_count() {
+        let (width, height) = (64, 40);
+        let data = test_data(width, height);
+        let original = Image::new_from_rgb(width, height, width * height, &data).unwrap();
+        for levels in 1..=8 {
+            assert!(
+                round_trip(&Dwt::new(Wavelet::Cdf97, levels), &original)
+                    == original.to_1d_vec().unwrap(),
+                "{}",
+                levels
+            );
+        }
+    }
+
+    #[test]
+    fn dwt_keeps_budget() {
+        let (width, height) = (513, 300);
+        let data = test_data(width, height);
+        for &number in &[0, 1, 1000, 40000] {
+            let image = Image::new_from_rgb(width, height, number, &data).unwrap();
+            let coefficients = Dwt::default().encode(&image).unwrap();
+            assert!(coefficients.kept_coefficients()[1] <= number);
+        }
+    }
+
+    #[test]
+    fn dwt_tiles() {
+        let (width, height) = (100, 70);
+        let data = test_data(width, height);
+        let original = Image::new_from_rgb(width, height, width * height, &data).unwrap();
+
+        let mut dwt = Dwt::default();
+        dwt.set_tile_size(Some((32, 32)));
+        assert!(round_trip(&dwt, &original) == original.to_1d_vec().unwrap());
+
+        let number = 500;
+        let mut image = original.clone();
+        image.set_coefficient(number);
+        dwt.set_tile_size(Some((64, 32)));
+        let coefficients = dwt.encode(&image).unwrap();
+        let frequencies = &coefficients.planes[0].samples;
+        let tiles = dwt.tiles(&image, &Channel::R);
+        let areas: Vec<usize> = tiles.iter().map(|&(_, w, _, h)| w * h).collect();
+        for (&(x_start, x_length, y_start, y_length), &budget) in
+            tiles.iter().zip(share(number, &areas).iter())
+        {
+            let kept = frequencies[y_start..y_start + y_length]
+                .iter()
+                .flat_map(|row| row[x_start..x_start + x_length].iter())
+                .filter(|f| **f != 0.0)
+                .count();
+            assert!(kept <= budget);
+        }
+    }
+
+    #[test]
+    fn largest_keeps_more_energy_than_zigzag() {
+        let (width, height) = (64, 64);
+        let data = test_data(width, height);
+        let original = Image::new_from_rgb(width, height, 64 * 5, &data).unwrap();
+
+        let energy = |image: &Image| -> (usize, f64) {
+            let coefficients = Dct::default().encode(image).unwrap();
+            let frequencies = &coefficients.planes[0].samples;
+            let kept = frequencies.iter().flatten().filter(|f| **f != 0.0).count();
+            let energy = frequencies.iter().flatten().map(|f| f * f).sum();
+            (kept, energy)
+        };
+
+        let (zigzag_kept, zigzag_energy) = energy(&original);
+        let mut largest = original.clone();
+        largest.set_selection(Selection::Largest);
+        let (largest_kept, largest_energy) = energy(&largest);
+        assert!(largest_kept <= 64 * 5);
+        assert!(zigzag_kept <= 64 * 5);
+        assert!(largest_energy >= zigzag_energy);
+
+        let mut largest = original.clone();
+        largest.set_selection(Selection::LargestAcrossChannels);
+        let coefficients = Dwt::default().encode(&largest).unwrap();
+        let kept = coefficients.kept_coefficients().iter().sum::<usize>();
+        assert!(kept <= 3 * 64 * 5);
+    }
+
+    #[test]
+    fn dct_budget_is_exact() {
+        let (width, height) = (20, 13);
+        let data = test_data(width, height);
+        let dct = Dct::default();
+        // 6 blocks, two 8x8, one 4x8, two 8x5 and one 4x5
+        for &number in &[1, 5, 7, 100, 170, 260, 10000] {
+            let image = Image::new_from_rgb(width, height, number, &data).unwrap();
+            let coefficients = dct.encode(&image).unwrap();
+            let numbers = dct.numbers(&image, &coefficients.planes[0], &Channel::R, number);
+            assert_eq!(
+                numbers.iter().sum::<usize>(),
+                std::cmp::min(number, width * height)
+            );
+            for (n, (_, w, _, h)) in numbers.iter().zip(dct.blocks(&image, &Channel::R)) {
+                assert!(*n <= w * h);
+            }
+            for &kept in coefficients.kept_coefficients().iter() {
+                assert!(kept <= number);
+            }
+        }
+    }
+
+    #[test]
+    fn color_transform_round_trip() {
+        let (width, height) = (24, 20);
+        let data = test_data(width, height);
+        let original = Image::new_from_rgb(width, height, width * height, &data).unwrap();
+
+        let mut image = original.clone();
+        image.set_color(ColorTransform::Rct);
+        let dwt = Dwt::new(Wavelet::Cdf53, 5);
+        assert!(round_trip(&dwt, &image) == original.to_1d_vec().unwrap());
+
+        for &color in &[ColorTransform::Ict, ColorTransform::Rct] {
+            let mut image = original.clone();
+            image.set_color(color);
+            for (a, b) in round_trip(&Dct::default(), &image)
+                .iter()
+                .zip(original.to_1d_vec().unwrap().iter())
+            {
+                assert!((i16::from(*a) - i16::from(*b)).abs() <= 1);
+            }
+        }
+    }
+
+    #[test]
+    fn dct_quantization() {
+        let (width, height) = (16, 16);
+        let data = test_data(width, height);
+        let original = Image::new_from_rgb(width, height, 1 << 20, &data).unwrap();
+        let encode = |image: &Image, quality: u8| {
+            let mut dct = Dct::default();
+            dct.set_quantization(Some(Quantization::jpeg(quality)));
+            dct.encode(image).unwrap()
+        };
+
+        let lossless = encode(&original, 100);
+        assert!(lossless
+            .planes
+            .iter()
+            .flat_map(|plane| plane.samples.iter().flatten())
+            .all(|f| *f == f.round()));
+
+        let coarse = encode(&original, 10);
+        let frequencies = &coarse.planes[0].samples;
+        let steps = QuantizationTable::luminance().scaled(10).steps(8, 8);
+        for y in 0..height {
+            for x in 0..width {
+                let q = steps[y % 8][x % 8];
+                let r = frequencies[y][x] / q;
+                assert!((r - r.round()).abs() < 1e-9);
+            }
+        }
+
+        let mut ycbcr = original.clone();
+        ycbcr.set_color(ColorTransform::Ict);
+        let ycbcr = encode(&ycbcr, 10);
+        let frequencies = &ycbcr.planes[1].samples;
+        let chroma_steps = QuantizationTable::chrominance().scaled(10).steps(8, 8);
+        for y in 0..height {
+            for x in 0..width {
+                let cb = frequencies[y][x] / chroma_steps[y % 8][x % 8];
+                assert!((cb - cb.round()).abs() < 1e-9);
+            }
+        }
+    }
+
+    #[test]
+    fn chroma_subsampling() {
+        let (width, height) = (37, 21);
+        // smooth content so that subsampled chroma stays close to the original
+        let data: Vec<u8> = (0..3 * width * height)
+            .map(|i| {
+                let (c, x, y) = (i / (width * height), i % width, i / width % height);
+                (20 + c * 40 + x * (c + 1) + y * 2) as u8
+            })
+            .collect();
+        let original = Image::new_from_rgb(width, height, 1 << 20, &data).unwrap();
+        let mut dwt = Dwt::default();
+        dwt.set_tile_size(Some((16, 16)));
+
+        for subsampling in Subsampling::iterator() {
+            for filter in Filter::iterator() {
+                for codec in &[&Dct::default() as &dyn Codec, &dwt] {
+                    let mut image = original.clone();
+                    image
+                        .set_color(ColorTransform::Ict)
+                        .set_subsampling(*subsampling)
+                        .set_filter(*filter);
+                    let coefficients = codec.encode(&image).unwrap();
+
+                    let (x_factor, y_factor) = subsampling.factors();
+                    let planes = &coefficients.planes;
+                    assert_eq!((planes[0].width, planes[0].height), (width, height));
+                    assert_eq!(
+                        (planes[2].width, planes[2].height),
+                        (width.div_ceil(x_factor), height.div_ceil(y_factor))
+                    );
+
+                    let image = codec.decode(&coefficients).unwrap();
+                    for (a, b) in image
+                        .to_1d_vec()
+                        .unwrap()
+                        .iter()
+                        .zip(original.to_1d_vec().unwrap().iter())
+                    {
+                        assert!((i16::from(*a) - i16::from(*b)).abs() <= 3);
+                    }
+                }
+            }
+        }
+
+        // subsampled chroma leaves more of the budget to luma
+        let number = 100;
+        let mut image = original.clone();
+        image
+            .set_coefficient(number)
+            .set_color(ColorTransform::Ict)
+            .set_subsampling(Subsampling::S420);
+        let kept = Dct::default().encode(&image).unwrap().kept_coefficients();
+        assert_eq!(image.plane_numbers().iter().sum::<usize>(), 3 * number);
+        assert!(kept[0] > number);
+        assert!(kept.iter().sum::<usize>() <= 3 * number);
+    }
+}
@@ -1,23 +1,889 @@
+use core::slice::Iter;
+use std::fmt;
+
+use serde::Deserialize;
+
+use crate::codec::Codec;
+use crate::error::Error;
+use crate::image::{
+    ColorTransform, Filter, Image, Order, RawLayout, Selection, Subsampling, Wavelet,
+};
+use crate::quantization::Quantization;
+use crate::rd;
+
+#[derive(Clone)]
 pub struct Config {
     pub path: std::path::PathBuf,
     //    pub path: String,
     pub coefficient: isize,
+    pub size: Option<(usize, usize)>,
+    // given when any of the layout options is, which reads the file as raw
+    pub raw_layout: Option<RawLayout>,
+    pub blocksize_x: usize,
+    pub blocksize_y: usize,
+    pub quality: Option<u8>,
+    pub qtable: Option<std::path::PathBuf>,
+    pub wavelet: Wavelet,
+    pub levels: usize,
+    pub tile_size: Option<(usize, usize)>,
+    pub selection: Selection,
+    pub color: ColorTransform,
+    pub subsampling: Subsampling,
+    pub filter: Filter,
+    pub headless: bool,
+    pub output: std::path::PathBuf,
+    pub rd: Option<rd::Format>,
+    pub plot: bool,
+    pub sweep: Sweep,
+}
+
+impl Default for Config {
+    // every option at its default, without an image or a coefficient yet
+    fn default() -> Self {
+        Config {
+            path: std::path::PathBuf::new(),
+            coefficient: -1,
+            size: None,
+            raw_layout: None,
+            blocksize_x: 8,
+            blocksize_y: 8,
+            quality: None,
+            qtable: None,
+            wavelet: Wavelet::Haar,
+            levels: 5,
+            tile_size: None,
+            selection: Selection::Zigzag,
+            color: ColorTransform::Rgb,
+            subsampling: Subsampling::S444,
+            filter: Filter::Box,
+            headless: false,
+            output: std::path::PathBuf::from("."),
+            rd: None,
+            plot: false,
+            sweep: Sweep::default(),
+        }
+    }
+}
+
+/*Options*/
+
+// name, value (empty for a switch) and description of every long option, in the order --help
+// lists them
+const OPTIONS: [(&str, &str, &str); 30] = [
+    ("input", "<path>", "image to compare, or the first argument"),
+    (
+        "coefficient",
+        "<n>",
+        "coefficients kept per channel, -1 sweeps, or the second argument",
+    ),
+    (
+        "config",
+        "<file>",
+        "TOML or JSON file of options, the command line wins over it",
+    ),
+    ("size", "<n|WxH>", "size of a raw file"),
+    (
+        "layout",
+        "<planar|interleaved>",
+        "sample layout of a raw file",
+    ),
+    ("depth", "<8|16>", "sample depth of a raw file"),
+    (
+        "endian",
+        "<big|little>",
+        "byte order of 16 bit samples of a raw file",
+    ),
+    ("order", "<rgb|bgr|gray>", "channel order of a raw file"),
+    ("blocksize", "<n|WxH>", "DCT block size (8)"),
+    (
+        "quality",
+        "<1-100>",
+        "quantize the DCT with the JPEG tables",
+    ),
+    ("qtable", "<file>", "quantize the DCT with custom tables"),
+    ("wavelet", "<haar|cdf53|cdf97>", "DWT wavelet (haar)"),
+    ("levels", "<n>", "DWT decomposition levels (5)"),
+    ("tile", "<n|WxH>", "DWT tile size"),
+    (
+        "selection",
+        "<zigzag|largest|largest-across>",
+        "which coefficients are kept (zigzag)",
+    ),
+    (
+        "color",
+        "<rgb|ict|rct>",
+        "color transform before coding (rgb)",
+    ),
+    (
+        "subsampling",
+        "<4:4:4|4:2:2|4:2:0>",
+        "chroma subsampling of ict or rct (4:4:4)",
+    ),
+    (
+        "filter",
+        "<box|bilinear>",
+        "chroma subsampling filter (box)",
+    ),
+    ("start", "<n>", "first budget of the sweep"),
+    ("end", "<n>", "last budget of the sweep"),
+    ("step", "<n>", "distance between the budgets of the sweep"),
+    ("count", "<n>", "number of budgets of the sweep (64)"),
+    (
+        "spacing",
+        "<linear|log>",
+        "spacing of the budgets of the sweep (linear)",
+    ),
+    ("budgets", "<n,n,...>", "sweep through these budgets"),
+    (
+        "bpp",
+        "<bpp,bpp,...>",
+        "sweep through these target bits per pixel",
+    ),
+    ("headless", "", "write the results without opening a window"),
+    (
+        "output",
+        "<dir>",
+        "directory of saved images and results (.)",
+    ),
+    ("rd", "<csv|json>", "write the rate-distortion table"),
+    ("plot", "", "plot the rate-distortion curves as SVG"),
+    ("help", "", "print this help"),
+];
+
+// the --help text
+pub fn usage() -> String {
+    let mut usage = "usage: gui_dct_dwt <path> <coefficient> [options]\n\noptions:\n".to_string();
+    for (name, value, description) in OPTIONS.iter() {
+        let option = format!("--{} {}", name, value);
+        usage += &format!("  {:<44} {}\n", option.trim_end(), description);
+    }
+    usage
+}
+
+// why the arguments were not taken, asking for --help ends the run the same way
+#[derive(Debug, PartialEq)]
+pub enum ArgsError {
+    Help,
+    Invalid(String),
+}
+
+impl fmt::Display for ArgsError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ArgsError::Help => write!(f, "{}", usage()),
+            ArgsError::Invalid(message) => write!(f, "{}", message),
+        }
+    }
+}
+
+impl From<&str> for ArgsError {
+    fn from(message: &str) -> Self {
+        ArgsError::Invalid(message.to_string())
+    }
+}
+
+impl From<String> for ArgsError {
+    fn from(message: String) -> Self {
+        ArgsError::Invalid(message)
+    }
+}
+
+/*Sweep*/
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Spacing {
+    Linear,
+    Log,
+}
+
+impl Spacing {
+    pub fn name(&self) -> &'static str {
+        match *self {
+            Spacing::Linear => "linear",
+            Spacing::Log => "log",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Spacing::iterator().find(|s| s.name() == name).cloned()
+    }
+
+    pub fn iterator() -> Iter<'static, Spacing> {
+        static SPACINGS: [Spacing; 2] = [Spacing::Linear, Spacing::Log];
+        SPACINGS.iter()
+    }
+}
+
+// the budgets compared when the coefficient is -1
+#[derive(Clone, Debug, PartialEq)]
+pub enum Sweep {
+    // count budgets spaced from start to end, or every step from start up to end, end defaults
+    // to every coefficient of a channel and start to a count-th of end
+    Range {
+        start: Option<usize>,
+        end: Option<usize>,
+        step: Option<usize>,
+        count: usize,
+        spacing: Spacing,
+    },
+    Budgets(Vec<usize>),
+    // target bits per pixel, each transform gets the largest budget within them
+    Bpp(Vec<f64>),
+}
+
+impl Default for Sweep {
+    // 64 evenly spaced budgets up to every coefficient
+    fn default() -> Self {
+        Sweep::Range {
+            start: None,
+            end: None,
+            step: None,
+            count: 64,
+            spacing: Spacing::Linear,
+        }
+    }
+}
+
+impl Sweep {
+    // the budgets of the sweep for image encoded by codec, only bits per pixel targets need the
+    // codec
+    pub fn budgets(&self, image: &Image, codec: &dyn Codec) -> Result<Vec<usize>, Error> {
+        match self {
+            Sweep::Bpp(targets) => targets
+                .iter()
+                .map(|&bpp| rd::budget_for_bpp(image, bpp, codec))
+                .collect(),
+            _ => {
+                let (width, height) = image.get_size();
+                Ok(self.coefficients(width * height))
+            }
+        }
+    }
+
+    // the budgets of a range or a list for channels of area coefficients, increasing and
+    // without repeats, empty for bits per pixel targets
+    pub fn coefficients(&self, area: usize) -> Vec<usize> {
+        let mut budgets: Vec<usize> = match self {
+            Sweep::Range {
+                start,
+                end,
+                step,
+                count,
+                spacing,
+            } => {
+                let end = end.unwrap_or(area).max(1);
+                let start = start.unwrap_or_else(|| end.div_ceil(*count)).clamp(1, end);
+                match (step, spacing) {
+                    (Some(step), _) => (start..=end).step_by(*step).collect(),
+                    _ if *count == 1 => vec![end],
+                    (None, Spacing::Linear) => (0..*count)
+                        .map(|i| start + (end - start) * i / (count - 1))
+                        .collect(),
+                    (None, Spacing::Log) => {
+                        let ratio = end as f64 / start as f64;
+                        (0..*count)
+                            .map(|i| {
+                                let exponent = i as f64 / (count - 1) as f64;
+                                (start as f64 * ratio.powf(exponent)).round() as usize
+                            })
+                            .collect()
+                    }
+                }
+            }
+            Sweep::Budgets(budgets) => budgets.clone(),
+            Sweep::Bpp(_) => Vec::new(),
+        };
+        budgets.sort_unstable();
+        budgets.dedup();
+        budgets
+    }
 }
 
 impl Config {
-    pub fn new(mut args: std::env::Args) -> Result<Self, &'static str> {
-        args.next();
+    pub fn new(args: std::env::Args) -> Result<Self, ArgsError> {
+        Config::from_args(&args.skip(1).collect::<Vec<_>>())
+    }
 
-        let path: std::path::PathBuf = match args.next() {
-            Some(arg) => std::path::PathBuf::from(arg),
-            None => return Err("Didn't get a path"),
+    // the options of the config file come first so that the command line overrides them
+    pub fn from_args(args: &[String]) -> Result<Self, ArgsError> {
+        let command_line = read_command_line(args)?;
+        if command_line.iter().any(|(name, _)| name == "help") {
+            return Err(ArgsError::Help);
+        }
+        let mut options = match command_line.iter().rev().find(|(name, _)| name == "config") {
+            Some((_, path)) => {
+                let content = std::fs::read_to_string(path)
+                    .map_err(|err| format!("Couldn't read the config file {}: {}", path, err))?;
+                read_config_file(std::path::Path::new(path), &content)?
+            }
+            None => Vec::new(),
         };
+        options.extend(command_line);
+
+        let mut config = Config::default();
+        let (mut path, mut coefficient) = (None, None);
+        for (name, value) in &options {
+            match name.as_str() {
+                "input" => path = Some(std::path::PathBuf::from(value)),
+                "coefficient" => match value.parse::<isize>() {
+                    Ok(c) if c >= -1 => coefficient = Some(c),
+                    _ => return Err("coefficient must be -1 or a number of coefficients".into()),
+                },
+                "config" => {}
+                _ => config.set(name, value)?,
+            }
+        }
+        config.path = path.ok_or("Didn't get a path, give it first or with --input")?;
+        config.coefficient =
+            coefficient.ok_or("Didn't get a coefficient, give it second or with --coefficient")?;
 
-        let coefficient: isize = match args.next() {
-            Some(arg) => isize::from_str_radix(&arg, 10).expect("not a valid number"),
-            None => return Err("Didn't get a coefficient"),
+        // only the Cb and Cr planes of YCbCr are subsampled
+        if config.subsampling != Subsampling::S444 && config.color == ColorTransform::Rgb {
+            return Err("chroma subsampling needs --color ict or rct".into());
+        }
+        if let Sweep::Range {
+            start: Some(start),
+            end: Some(end),
+            ..
+        } = config.sweep
+        {
+            if start > end {
+                return Err("sweep start must not be after its end".into());
+            }
+        }
+        Ok(config)
+    }
+
+    // the layout options start from the default planar 8 bit RGB
+    fn raw_layout_mut(&mut self) -> &mut RawLayout {
+        self.raw_layout.get_or_insert_with(RawLayout::default)
+    }
+
+    // one option by its long name, the value of a switch is true or false
+    fn set(&mut self, name: &str, value: &str) -> Result<(), ArgsError> {
+        match name {
+            "size" => self.size = Some(parse_blocksize(value).ok_or("not a valid image size")?),
+            "layout" => {
+                self.raw_layout_mut().interleaved = match value {
+                    "planar" => false,
+                    "interleaved" => true,
+                    _ => return Err("raw layout must be one of planar, interleaved".into()),
+                }
+            }
+            "depth" => match value.parse::<usize>() {
+                Ok(d) if d == 8 || d == 16 => self.raw_layout_mut().depth = d,
+                _ => return Err("sample depth must be 8 or 16".into()),
+            },
+            "endian" => {
+                self.raw_layout_mut().big_endian = match value {
+                    "big" => true,
+                    "little" => false,
+                    _ => return Err("endianness must be one of big, little".into()),
+                }
+            }
+            "order" => {
+                self.raw_layout_mut().order =
+                    Order::from_name(value).ok_or("channel order must be one of rgb, bgr, gray")?
+            }
+            "blocksize" => {
+                let (x, y) = parse_blocksize(value).ok_or("not a valid blocksize")?;
+                self.blocksize_x = x;
+                self.blocksize_y = y;
+            }
+            "quality" => match value.parse::<u8>() {
+                Ok(q) if (1..=100).contains(&q) => self.quality = Some(q),
+                _ => return Err("quality must be between 1 and 100".into()),
+            },
+            "qtable" => self.qtable = Some(std::path::PathBuf::from(value)),
+            "wavelet" => {
+                self.wavelet =
+                    Wavelet::from_name(value).ok_or("wavelet must be one of haar, cdf53, cdf97")?
+            }
+            "levels" => self.levels = parse_at_least(value, 1, "levels must be at least 1")?,
+            "tile" => self.tile_size = Some(parse_blocksize(value).ok_or("not a valid tile size")?),
+            "selection" => {
+                self.selection = Selection::from_name(value)
+                    .ok_or("selection must be one of zigzag, largest, largest-across")?
+            }
+            "color" => {
+                self.color = ColorTransform::from_name(value)
+                    .ok_or("color transform must be one of rgb, ict, rct")?
+            }
+            "subsampling" => {
+                self.subsampling = Subsampling::from_name(value)
+                    .ok_or("chroma subsampling must be one of 4:4:4, 4:2:2, 4:2:0")?
+            }
+            "filter" => {
+                self.filter =
+                    Filter::from_name(value).ok_or("filter must be one of box, bilinear")?
+            }
+            "start" | "end" | "step" | "count" | "spacing" => {
+                // the last of a range and a list of budgets or targets wins
+                if let Sweep::Budgets(_) | Sweep::Bpp(_) = self.sweep {
+                    self.sweep = Sweep::default();
+                }
+                if let Sweep::Range {
+                    start,
+                    end,
+                    step,
+                    count,
+                    spacing,
+                } = &mut self.sweep
+                {
+                    match name {
+                        "start" => {
+                            *start =
+                                Some(parse_at_least(value, 1, "sweep start must be at least 1")?)
+                        }
+                        "end" => {
+                            *end = Some(parse_at_least(value, 1, "sweep end must be at least 1")?)
+                        }
+                        "step" => {
+                            *step = Some(parse_at_least(value, 1, "sweep step must be at least 1")?)
+                        }
+                        "count" => {
+                            *count = parse_at_least(value, 1, "sweep count must be at least 1")?
+                        }
+                        _ => {
+                            *spacing = Spacing::from_name(value)
+                                .ok_or("spacing must be one of linear, log")?
+                        }
+                    }
+                }
+            }
+            "budgets" => {
+                let budgets = parse_list::<usize>(value)
+                    .filter(|budgets| budgets.iter().all(|&b| b >= 1))
+                    .ok_or("budgets must be a comma separated list of numbers from 1")?;
+                self.sweep = Sweep::Budgets(budgets);
+            }
+            "bpp" => {
+                let targets = parse_list::<f64>(value)
+                    .filter(|targets| targets.iter().all(|&t| t > 0.0 && t.is_finite()))
+                    .ok_or("bpp must be a comma separated list of positive numbers")?;
+                self.sweep = Sweep::Bpp(targets);
+            }
+            "headless" => self.headless = parse_switch(name, value)?,
+            "output" => self.output = std::path::PathBuf::from(value),
+            "rd" => {
+                self.rd = Some(
+                    rd::Format::from_name(value)
+                        .ok_or("rate-distortion format must be one of csv, json")?,
+                )
+            }
+            "plot" => self.plot = parse_switch(name, value)?,
+            _ => return Err(format!("unknown option {}, see --help", name).into()),
+        }
+        Ok(())
+    }
+
+    // the coefficient budgets to compare for image encoded by codec, -1 sweeps
+    pub fn budgets(&self, image: &Image, codec: &dyn Codec) -> Result<Vec<usize>, Error> {
+        if self.coefficient == -1 {
+            self.sweep.budgets(image, codec)
+        } else {
+            Ok(vec![self.coefficient as usize])
+        }
+    }
+
+    // no quantization unless a quality or a custom table is given, a custom table on its own
+    // is used as is (quality 50)
+    pub fn quantization(&self) -> Result<Option<Quantization>, std::io::Error> {
+        match (&self.qtable, self.quality) {
+            (Some(path), quality) => Quantization::from_file(path, quality.unwrap_or(50)).map(Some),
+            (None, Some(quality)) => Ok(Some(Quantization::jpeg(quality))),
+            (None, None) => Ok(None),
+        }
+    }
+}
+
+// (name, value) pairs of the arguments in order, the first two arguments that are not options
+// are the input and the coefficient; --name=value works too and a switch without a value is
+// true
+fn read_command_line(args: &[String]) -> Result<Vec<(String, String)>, ArgsError> {
+    let mut options = Vec::new();
+    let mut positional = ["input", "coefficient"].iter();
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        if arg == "-h" {
+            options.push(("help".to_string(), "true".to_string()));
+            continue;
+        }
+        let option = match arg.strip_prefix("--") {
+            Some(option) => option,
+            None => {
+                let name = positional
+                    .next()
+                    .ok_or_else(|| format!("unexpected argument {}, see --help", arg))?;
+                options.push((name.to_string(), arg.clone()));
+                continue;
+            }
+        };
+
+        let (name, inline) = match option.find('=') {
+            Some(i) => (&option[..i], Some(&option[i + 1..])),
+            None => (option, None),
+        };
+        let value = match OPTIONS.iter().find(|(n, _, _)| *n == name) {
+            None => return Err(format!("unknown option --{}, see --help", name).into()),
+            Some((_, "", _)) => inline.unwrap_or("true").to_string(),
+            Some((_, value, _)) => match inline {
+                Some(inline) => inline.to_string(),
+                None => args
+                    .next()
+                    .ok_or_else(|| format!("Didn't get a value {} for --{}", value, name))?
+                    .clone(),
+            },
         };
+        options.push((name.to_string(), value));
+    }
+    Ok(options)
+}
+
+fn parse_at_least(value: &str, minimum: usize, message: &str) -> Result<usize, ArgsError> {
+    match value.parse::<usize>() {
+        Ok(v) if v >= minimum => Ok(v),
+        _ => Err(message.into()),
+    }
+}
+
+fn parse_switch(name: &str, value: &str) -> Result<bool, ArgsError> {
+    match value {
+        "true" => Ok(true),
+        "false" => Ok(false),
+        _ => Err(format!("{} must be true or false", name).into()),
+    }
+}
+
+// "4096,8192,16384"
+fn parse_list<T: std::str::FromStr>(value: &str) -> Option<Vec<T>> {
+    let list: Option<Vec<T>> = value.split(',').map(|v| v.trim().parse().ok()).collect();
+    list.filter(|list| !list.is_empty())
+}
+
+// "16" for a square block, tile or image, "16x8" for width x height
+fn parse_blocksize(value: &str) -> Option<(usize, usize)> {
+    let mut sides = value.splitn(2, 'x');
+    let x: usize = sides.next()?.parse().ok()?;
+    let y: usize = match sides.next() {
+        Some(side) => side.parse().ok()?,
+        None => x,
+    };
+    if x == 0 || y == 0 {
+        return None;
+    }
+    Some((x, y))
+}
+
+/*Config Files*/
+
+// the options a TOML or JSON config file may set, keyed by the long option names, e.g.
+//
+//     wavelet = "cdf97"                      {"wavelet": "cdf97",
+//     budgets = [4096, 16384, 65536]          "budgets": [4096, 16384, 65536],
+//     headless = true                         "headless": true}
+#[derive(Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct FileOptions {
+    input: Option<String>,
+    coefficient: Option<isize>,
+    size: Option<Size>,
+    layout: Option<String>,
+    depth: Option<usize>,
+    endian: Option<String>,
+    order: Option<String>,
+    blocksize: Option<Size>,
+    quality: Option<u8>,
+    qtable: Option<String>,
+    wavelet: Option<String>,
+    levels: Option<usize>,
+    tile: Option<Size>,
+    selection: Option<String>,
+    color: Option<String>,
+    subsampling: Option<String>,
+    filter: Option<String>,
+    start: Option<usize>,
+    end: Option<usize>,
+    step: Option<usize>,
+    count: Option<usize>,
+    spacing: Option<String>,
+    budgets: Option<Vec<usize>>,
+    bpp: Option<Vec<f64>>,
+    headless: Option<bool>,
+    output: Option<String>,
+    rd: Option<String>,
+    plot: Option<bool>,
+}
+
+// a size is a side of a square, 8, or <x>x<y> as on the command line, "16x8"
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum Size {
+    Side(usize),
+    Sides(String),
+}
+
+impl Size {
+    fn to_option(&self) -> String {
+        match self {
+            Size::Side(side) => side.to_string(),
+            Size::Sides(sides) => sides.clone(),
+        }
+    }
+}
+
+impl FileOptions {
+    // the same (name, value) pairs the command line gives, in the order of --help, so a list of
+    // budgets wins over a range given in the same file
+    fn into_options(self) -> Vec<(String, String)> {
+        fn list<T: ToString>(values: &[T]) -> String {
+            values
+                .iter()
+                .map(T::to_string)
+                .collect::<Vec<_>>()
+                .join(",")
+        }
+        let options = [
+            ("input", self.input),
+            ("coefficient", self.coefficient.map(|c| c.to_string())),
+            ("size", self.size.map(|s| s.to_option())),
+            ("layout", self.layout),
+            ("depth", self.depth.map(|d| d.to_string())),
+            ("endian", self.endian),
+            ("order", self.order),
+            ("blocksize", self.blocksize.map(|s| s.to_option())),
+            ("quality", self.quality.map(|q| q.to_string())),
+            ("qtable", self.qtable),
+            ("wavelet", self.wavelet),
+            ("levels", self.levels.map(|l| l.to_string())),
+            ("tile", self.tile.map(|s| s.to_option())),
+            ("selection", self.selection),
+            ("color", self.color),
+            ("subsampling", self.subsampling),
+            ("filter", self.filter),
+            ("start", self.start.map(|s| s.to_string())),
+            ("end", self.end.map(|e| e.to_string())),
+            ("step", self.step.map(|s| s.to_string())),
+            ("count", self.count.map(|c| c.to_string())),
+            ("spacing", self.spacing),
+            ("budgets", self.budgets.map(|b| list(&b))),
+            ("bpp", self.bpp.map(|b| list(&b))),
+            ("headless", self.headless.map(|h| h.to_string())),
+            ("output", self.output),
+            ("rd", self.rd),
+            ("plot", self.plot.map(|p| p.to_string())),
+        ];
+        options
+            .iter()
+            .filter_map(|(name, value)| Some((name.to_string(), value.clone()?)))
+            .collect()
+    }
+}
+
+// the (name, value) pairs of a config file, a file ending in .json or starting with { is JSON,
+// anything else TOML
+fn read_config_file(
+    path: &std::path::Path,
+    content: &str,
+) -> Result<Vec<(String, String)>, ArgsError> {
+    let json =
+        path.extension().is_some_and(|e| e == "json") || content.trim_start().starts_with('{');
+    let options: Result<FileOptions, String> = if json {
+        serde_json::from_str(content).map_err(|err| err.to_string())
+    } else {
+        toml::from_str(content).map_err(|err| err.to_string())
+    };
+    let options =
+        options.map_err(|message| format!("config file {}: {}", path.display(), message))?;
+    Ok(options.into_options())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn range(start: Option<usize>, end: Option<usize>, step: Option<usize>, count: usize) -> Sweep {
+        Sweep::Range {
+            start,
+            end,
+            step,
+            count,
+            spacing: Spacing::Linear,
+        }
+    }
+
+    #[test]
+    fn sweep_budgets() {
+        // the default is the 64 budgets of a 512x512 image
+        let budgets = Sweep::default().coefficients(512 * 512);
+        assert_eq!(budgets.len(), 64);
+        assert_eq!(
+            (budgets[0], budgets[1], budgets[63]),
+            (4096, 8192, 512 * 512)
+        );
+        // smaller images end at every coefficient too
+        assert_eq!(Sweep::default().coefficients(20 * 13).last(), Some(&260));
+
+        assert_eq!(
+            range(Some(10), Some(50), None, 5).coefficients(100),
+            [10, 20, 30, 40, 50]
+        );
+        assert_eq!(
+            range(Some(10), None, Some(40), 5).coefficients(100),
+            [10, 50, 90]
+        );
+        assert_eq!(range(None, Some(50), None, 1).coefficients(100), [50]);
+        assert_eq!(range(Some(500), None, None, 3).coefficients(100), [100]);
+
+        let log = Sweep::Range {
+            start: Some(1),
+            end: Some(10000),
+            step: None,
+            count: 5,
+            spacing: Spacing::Log,
+        };
+        assert_eq!(log.coefficients(1 << 20), [1, 10, 100, 1000, 10000]);
+        assert_eq!(Sweep::Budgets(vec![30, 10, 30]).coefficients(100), [10, 30]);
+    }
+
+    fn args(args: &[&str]) -> Vec<String> {
+        args.iter().map(|arg| arg.to_string()).collect()
+    }
+
+    #[test]
+    fn command_line() {
+        let config =
+            Config::from_args(&args(&["a.rgb", "-1", "--wavelet=cdf97", "--headless"])).unwrap();
+        assert_eq!(config.path, std::path::PathBuf::from("a.rgb"));
+        assert_eq!(config.coefficient, -1);
+        assert_eq!(config.wavelet, Wavelet::Cdf97);
+        assert!(config.headless);
+
+        let config = Config::from_args(&args(&[
+            "--coefficient",
+            "4096",
+            "--input",
+            "a.png",
+            "--budgets",
+            "10,20",
+            "--count",
+            "5",
+        ]))
+        .unwrap();
+        assert_eq!((config.coefficient, config.blocksize_x), (4096, 8));
+        // the last of a list and a range wins
+        assert_eq!(config.sweep.coefficients(100), [20, 40, 60, 80, 100]);
+        assert_eq!(config.raw_layout, None);
+        // a layout option reads the file as raw even when it gives the default
+        let config = Config::from_args(&args(&["a.png", "1", "--order", "rgb"])).unwrap();
+        assert_eq!(config.raw_layout, Some(RawLayout::default()));
+
+        assert_eq!(
+            Config::from_args(&args(&["a.rgb", "--help"])).err(),
+            Some(ArgsError::Help)
+        );
+        for wrong in [
+            &["a.rgb"][..],
+            &["a.rgb", "x"],
+            &["a.rgb", "-2"],
+            &["a.rgb", "1", "b"],
+            &["a.rgb", "1", "--levels"],
+            &["a.rgb", "1", "--levels", "0"],
+            &["a.rgb", "1", "--colour", "ict"],
+            &["a.rgb", "1", "--plot=yes"],
+            &["a.rgb", "1", "--start", "9", "--end", "3"],
+            &["a.rgb", "1", "--subsampling", "4:2:0"],
+        ]
+        .iter()
+        {
+            assert!(matches!(
+                Config::from_args(&args(wrong)),
+                Err(ArgsError::Invalid(_))
+            ));
+        }
+        assert!(usage().contains("--spacing <linear|log>"));
+    }
+
+    #[test]
+    fn config_files() {
+        let toml = "# sweep
+wavelet = \"cdf97\"
+budgets = [
+    4096,
+    8192, # two
+]
+headless = true
+output = 'out # 1'
+blocksize = \"16x8\"
+tile = 32
+";
+        let options = read_config_file(std::path::Path::new("a.toml"), toml).unwrap();
+        let expected = [
+            ("blocksize", "16x8"),
+            ("wavelet", "cdf97"),
+            ("tile", "32"),
+            ("budgets", "4096,8192"),
+            ("headless", "true"),
+            ("output", "out # 1"),
+        ];
+        let expected: Vec<(String, String)> = expected
+            .iter()
+            .map(|(n, v)| (n.to_string(), v.to_string()))
+            .collect();
+        assert_eq!(options, expected);
+
+        let json = "{\"wavelet\": \"cdf97\", \"budgets\": [4096, 8192],
+            \"headless\": true, \"output\": \"out # 1\", \"blocksize\": \"16x8\", \"tile\": 32}";
+        assert_eq!(
+            read_config_file(std::path::Path::new("a.json"), json).unwrap(),
+            expected
+        );
+        let escaped = "{\"output\": \"\\u00e9t\\\"e # 1\"}";
+        assert_eq!(
+            read_config_file(std::path::Path::new("a.json"), escaped).unwrap(),
+            vec![("output".to_string(), "\u{e9}t\"e # 1".to_string())]
+        );
+        let escaped = "output = \"a\\\" # b\" # c";
+        assert_eq!(
+            read_config_file(std::path::Path::new("a.toml"), escaped).unwrap(),
+            vec![("output".to_string(), "a\" # b".to_string())]
+        );
+
+        let path = std::path::Path::new("a.toml");
+        assert!(read_config_file(path, "wavelet").is_err());
+        assert!(read_config_file(path, "wavelet = haar").is_err());
+        assert!(read_config_file(path, "levels = 3\nlevels = 4").is_err());
+        assert!(read_config_file(path, "[codec]\nwavelet = 'haar'").is_err());
+        assert!(read_config_file(path, "config = 'other.toml'").is_err());
+        assert!(read_config_file(path, "colour = 'ict'").is_err());
+        assert!(read_config_file(path, "{\"levels\": \"three\"}").is_err());
+        assert!(read_config_file(path, "{\"levels\": null}")
+            .unwrap()
+            .is_empty());
+        assert!(read_config_file(path, "{\"levels\": 3").is_err());
+        assert!(read_config_file(path, "{\"levels\": 3, \"levels\": 4}").is_err());
+        assert_eq!(parse_list::<usize>("1, 2,3"), Some(vec![1, 2, 3]));
+        assert_eq!(parse_list::<f64>("0.5,x"), None);
+    }
 
-        Ok(Self { path, coefficient })
+    #[test]
+    fn command_line_overrides_the_file() {
+        let path = std::env::temp_dir().join("dct_dwt_config_test.json");
+        std::fs::write(
+            &path,
+            "{\"input\": \"a.rgb\", \"coefficient\": 64, \"levels\": 3, \"wavelet\": \"cdf53\"}",
+        )
+        .unwrap();
+        let config = Config::from_args(&args(&[
+            "--config",
+            path.to_str().unwrap(),
+            "--levels",
+            "4",
+        ]))
+        .unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(config.path, std::path::PathBuf::from("a.rgb"));
+        assert_eq!((config.coefficient, config.levels), (64, 4));
+        assert_eq!(config.wavelet, Wavelet::Cdf53);
     }
 }
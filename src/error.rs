@@ -0,0 +1,56 @@
+use std::fmt;
+
+/*Errors*/
+
+#[derive(Debug)]
+pub enum Error {
+    // an empty image, or two images of different sizes compared
+    InvalidDimensions(String),
+    // raw data of another length than its size and layout take
+    ShortInput {
+        width: usize,
+        height: usize,
+        expected: usize,
+        actual: usize,
+    },
+    UnsupportedLayout(String),
+    // coefficients decoded by another codec, or with other parameters, than encoded them
+    CodecMismatch(String),
+    Io(std::io::Error),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::InvalidDimensions(message) => write!(f, "{}", message),
+            Error::ShortInput {
+                width,
+                height,
+                expected,
+                actual,
+            } => write!(
+                f,
+                "a {}x{} image takes {} bytes, got {}",
+                width, height, expected, actual
+            ),
+            Error::UnsupportedLayout(message) => write!(f, "{}", message),
+            Error::CodecMismatch(message) => write!(f, "{}", message),
+            Error::Io(err) => write!(f, "{}", err),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Io(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl From<std::io::Error> for Error {
+    fn from(err: std::io::Error) -> Self {
+        Error::Io(err)
+    }
+}
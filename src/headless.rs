@@ -0,0 +1,180 @@
+use std::io::Write;
+
+use crate::codec;
+use crate::config::Config;
+use crate::error::Error;
+use crate::image::Image;
+use crate::rd::{self, Point};
+
+/*Comparison Without a Window*/
+
+// encode and decode the image with every codec for every budget of the configuration,
+// the reconstructions are written as <name>_<codec>_<coefficient>.ppm, e.g. <name>_dct_64.ppm
+// and <name>_dwt_haar_64.ppm, and the kept coefficients with the MSE, PSNR, SSIM,
+// MS-SSIM, blockiness and ringing as <name>_metrics.csv, all in the output directory, along
+// with the rate-distortion table and plot when asked for, a line per reconstruction goes to
+// progress
+pub fn run<W: Write>(
+    config: &Config,
+    image_rgb: &Image,
+    name: &str,
+    progress: &mut W,
+) -> Result<(), Error> {
+    std::fs::create_dir_all(&config.output)?;
+
+    let names = image_rgb.channel_names();
+    let mut metrics = format!(
+        "codec,coefficient,kept_{},kept_{},kept_{}",
+        names[0], names[1], names[2]
+    );
+    for metric in ["mse", "psnr", "ssim", "ms_ssim", "blockiness", "ringing"].iter() {
+        metrics += &format!(",{0}_r,{0}_g,{0}_b,{0}", metric);
+    }
+    metrics += "\n";
+    let mut points = Vec::new();
+
+    // with bits per pixel targets every codec gets budgets of its own
+    let codecs = codec::all(config)?;
+    let budgets = codecs
+        .iter()
+        .map(|codec| config.budgets(image_rgb, codec.as_ref()))
+        .collect::<Result<Vec<_>, Error>>()?;
+
+    for i in 0..budgets[0].len() {
+        for (codec, budgets) in codecs.iter().zip(&budgets) {
+            let coefficient = budgets[i];
+            let (coefficients, image) = codec.reconstruct(image_rgb, coefficient)?;
+            let codec = codec.tag();
+            let path = config
+                .output
+                .join(format!("{}_{}_{}.ppm", name, codec, coefficient));
+            image.save_ppm(&path)?;
+            let kept = coefficients.kept_coefficients();
+            let mse = image.mse(image_rgb)?;
+            let psnr = image.psnr(image_rgb)?;
+            let ssim = image.ssim(image_rgb)?;
+            points.push(Point::new(
+                &codec,
+                &coefficients,
+                psnr.overall,
+                ssim.overall,
+            ));
+            let ms_ssim = image.ms_ssim(image_rgb)?;
+            let blockiness = image.blockiness(config.blocksize_x, config.blocksize_y)?;
+            let ringing = image.ringing(image_rgb)?;
+            metrics += &format!(
+                "{},{},{},{},{}",
+                codec, coefficient, kept[0], kept[1], kept[2]
+            );
+            for measure in [mse, psnr, ssim, ms_ssim, blockiness, ringing].iter() {
+                for value in measure.channels.iter().chain(&[measure.overall]) {
+                    metrics += &format!(",{:.4}", value);
+                }
+            }
+            metrics += "\n";
+            writeln!(
+                progress,
+                "{} {}: kept {} {} / {} {} / {} {}, PSNR {:.2} dB, SSIM {:.4}, MS-SSIM {:.4}",
+                codec,
+                coefficient,
+                names[0],
+                kept[0],
+                names[1],
+                kept[1],
+                names[2],
+                kept[2],
+                psnr.overall,
+                ssim.overall,
+                ms_ssim.overall
+            )?;
+        }
+    }
+
+    let path = config.output.join(format!("{}_metrics.csv", name));
+    std::fs::write(path, metrics)?;
+    Ok(rd::write(config, name, &points)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::input;
+
+    fn config(output: &std::path::Path, path: &std::path::Path) -> Config {
+        let args: Vec<String> = [
+            path.to_str().unwrap(),
+            "-1",
+            "--headless",
+            "--budgets",
+            "16,64",
+        ]
+        .iter()
+        .chain(&["--output", output.to_str().unwrap()])
+        .map(|a| a.to_string())
+        .collect();
+        Config::from_args(&args).unwrap()
+    }
+
+    #[test]
+    fn writes_reconstructions_and_metrics() {
+        let directory = std::env::temp_dir().join(format!("headless_test_{}", std::process::id()));
+        std::fs::create_dir_all(&directory).unwrap();
+        let path = directory.join("gradient.ppm");
+        let mut content = b"P6\n16 16\n255\n".to_vec();
+        for i in 0..16 * 16 * 3 {
+            content.push((i * 7 % 256) as u8);
+        }
+        std::fs::write(&path, &content).unwrap();
+
+        let output = directory.join("out");
+        let config = config(&output, &path);
+        let (width, height, data, layout) =
+            input::read(&config.path, config.size, config.raw_layout.as_ref()).unwrap();
+        let image = Image::new_from_raw(width, height, 0, &data, &layout).unwrap();
+        let mut progress = Vec::new();
+        run(&config, &image, "gradient", &mut progress).unwrap();
+
+        for file in ["dct_16", "dct_64", "dwt_haar_16", "dwt_haar_64"].iter() {
+            let saved = output.join(format!("gradient_{}.ppm", file));
+            let (width, height, _, _) = input::read(&saved, None, None).unwrap();
+            assert_eq!((width, height), (16, 16));
+        }
+        let metrics = std::fs::read_to_string(output.join("gradient_metrics.csv")).unwrap();
+        let lines: Vec<&str> = metrics.lines().collect();
+        assert_eq!(lines.len(), 5);
+        assert!(lines[0].starts_with("codec,coefficient,kept_R,kept_G,kept_B,mse_r"));
+        let columns = lines[0].split(',').count();
+        assert!(lines[1..]
+            .iter()
+            .all(|line| line.split(',').count() == columns));
+        // the rows follow the budgets, keeping at most the budget of nonzero coefficients
+        let rows: Vec<(&str, usize, usize)> = lines[1..]
+            .iter()
+            .map(|line| {
+                let fields: Vec<&str> = line.split(',').collect();
+                let kept = fields[2..5].iter().map(|k| k.parse().unwrap()).max();
+                (fields[0], fields[1].parse().unwrap(), kept.unwrap())
+            })
+            .collect();
+        assert_eq!(
+            rows.iter().map(|row| (row.0, row.1)).collect::<Vec<_>>(),
+            vec![("dct", 16), ("dwt_haar", 16), ("dct", 64), ("dwt_haar", 64)]
+        );
+        assert!(rows.iter().all(|row| row.2 > 0 && row.2 <= row.1));
+        // a progress line per reconstruction
+        assert_eq!(String::from_utf8(progress).unwrap().lines().count(), 4);
+
+        std::fs::remove_dir_all(&directory).unwrap();
+    }
+
+    #[test]
+    fn output_that_is_a_file() {
+        // an output directory that is a file fails instead of panicking
+        let path = std::env::temp_dir().join(format!("headless_test_{}.out", std::process::id()));
+        std::fs::write(&path, b"").unwrap();
+        let image = Image::new_from_rgb(2, 2, 0, &[0; 12]).unwrap();
+        let result = run(&config(&path, &path), &image, "a", &mut Vec::new());
+        std::fs::remove_file(&path).unwrap();
+        assert!(result.is_err());
+    }
+}
@@ -1,29 +1,65 @@
-use self::channel::Channel;
 use self::pixel::Pixel;
+use crate::error::Error;
+use crate::metrics::{self, Measure};
+
+pub(crate) use self::channel::Channel;
+pub(crate) use self::plane::Plane;
+pub(crate) use self::selection::keep_largest;
+
+pub use self::color::ColorTransform;
+pub use self::dwt::Wavelet;
+pub use self::layout::{Order, RawLayout};
+pub use self::selection::Selection;
+pub use self::subsampling::{Filter, Subsampling};
 
 mod channel {
 
     use core::slice::Iter;
 
+    // Y, Cb and Cr name the components of a color transformed pixel, they are stored in the
+    // same places as R, G and B
     pub enum Channel {
         R,
         G,
         B,
+        Y,
+        Cb,
+        Cr,
     }
 
     impl Channel {
         pub fn to_number(&self) -> usize {
             match *self {
-                Channel::R => 0,
-                Channel::G => 1,
-                Channel::B => 2,
+                Channel::R | Channel::Y => 0,
+                Channel::G | Channel::Cb => 1,
+                Channel::B | Channel::Cr => 2,
+            }
+        }
+
+        pub fn name(&self) -> &'static str {
+            match *self {
+                Channel::R => "R",
+                Channel::G => "G",
+                Channel::B => "B",
+                Channel::Y => "Y",
+                Channel::Cb => "Cb",
+                Channel::Cr => "Cr",
             }
         }
 
+        pub fn is_chroma(&self) -> bool {
+            matches!(*self, Channel::Cb | Channel::Cr)
+        }
+
         pub fn iterator() -> Iter<'static, Channel> {
             static CHANNELS: [Channel; 3] = [Channel::R, Channel::G, Channel::B];
             CHANNELS.iter()
         }
+
+        pub fn ycbcr_iterator() -> Iter<'static, Channel> {
+            static CHANNELS: [Channel; 3] = [Channel::Y, Channel::Cb, Channel::Cr];
+            CHANNELS.iter()
+        }
     }
 }
 
@@ -45,9 +81,9 @@ mod pixel {
 
         fn index(&self, c: &Channel) -> &T {
             match *c {
-                Channel::R => &self.r,
-                Channel::G => &self.g,
-                Channel::B => &self.b,
+                Channel::R | Channel::Y => &self.r,
+                Channel::G | Channel::Cb => &self.g,
+                Channel::B | Channel::Cr => &self.b,
             }
         }
     }
@@ -55,9 +91,9 @@ mod pixel {
     impl<T> IndexMut<&Channel> for Pixel<T> {
         fn index_mut(&mut self, c: &Channel) -> &mut T {
             match *c {
-                Channel::R => &mut self.r,
-                Channel::G => &mut self.g,
-                Channel::B => &mut self.b,
+                Channel::R | Channel::Y => &mut self.r,
+                Channel::G | Channel::Cb => &mut self.g,
+                Channel::B | Channel::Cr => &mut self.b,
             }
         }
     }
@@ -72,598 +108,2208 @@ mod pixel {
             assert_eq!(pixel[&Channel::R], 14);
             assert_eq!(pixel[&Channel::G], 9);
             assert_eq!(pixel[&Channel::B], 10);
+            assert_eq!(pixel[&Channel::Y], 14);
+            assert_eq!(pixel[&Channel::Cb], 9);
+            assert_eq!(pixel[&Channel::Cr], 10);
         }
+    }
+}
+
+mod plane {
 
+    // one coded component, the chroma planes are smaller than the image when subsampled
+    #[derive(Clone)]
+    pub struct Plane {
+        pub width: usize,
+        pub height: usize,
+        pub samples: Vec<Vec<f64>>,
     }
 
+    impl Plane {
+        pub fn new(width: usize, height: usize) -> Self {
+            Self {
+                width,
+                height,
+                samples: vec![vec![0.0; width]; height],
+            }
+        }
+    }
 }
 
-mod dct {
+mod layout {
 
-    use super::channel::Channel;
-    use super::pixel::Pixel;
-    use crate::zigzag::Zigzag;
+    use core::slice::Iter;
 
-    /*Discrete Cosine Transformation Implementation*/
+    use super::channel::Channel;
 
-    fn dct(
-        block: &[Vec<Pixel<u8>>],
-        dct_block: &mut Vec<Vec<Pixel<f64>>>,
-        u: usize,
-        v: usize,
-        c: &Channel,
-        x_start: usize,
-        x_length: usize,
-        y_start: usize,
-        y_length: usize,
-    ) {
-        let c_uv: f64 = match (u, v) {
-            (0, 0) => 1.0 / 2.0,
-            (0, _) => ((1.0 / 2.0) as f64).sqrt(),
-            (_, 0) => ((1.0 / 2.0) as f64).sqrt(),
-            _ => 1.0,
-        };
+    /*Raw Sample Layouts*/
 
-        let mut f_uv = 0.0;
-        for y in 0..y_length {
-            for x in 0..x_length {
-                let f_xy = block[y + y_start][x + x_start][c];
+    #[derive(Clone, Copy, Debug, PartialEq)]
+    pub enum Order {
+        Rgb,
+        Bgr,
+        Gray, // a single channel copied to R, G and B
+    }
 
-                f_uv += (f64::from(f_xy))
-                    * ((((2 * x + 1) * u) as f64) * std::f64::consts::PI / 16.0).cos()
-                    * ((((2 * y + 1) * v) as f64) * std::f64::consts::PI / 16.0).cos();
+    impl Order {
+        pub fn name(&self) -> &'static str {
+            match *self {
+                Order::Rgb => "rgb",
+                Order::Bgr => "bgr",
+                Order::Gray => "gray",
             }
         }
 
-        f_uv *= 1.0 / 4.0 * c_uv;
+        pub fn from_name(name: &str) -> Option<Self> {
+            Self::iterator().find(|o| o.name() == name).copied()
+        }
 
-        dct_block[v + y_start][u + x_start][c] = f_uv;
-    }
+        pub fn iterator() -> Iter<'static, Order> {
+            static ORDERS: [Order; 3] = [Order::Rgb, Order::Bgr, Order::Gray];
+            ORDERS.iter()
+        }
 
-    fn idct(
-        block: &mut Vec<Vec<Pixel<u8>>>,
-        dct_block: &[Vec<Pixel<f64>>],
-        x: usize,
-        y: usize,
-        c: &Channel,
-        x_start: usize,
-        x_length: usize,
-        y_start: usize,
-        y_length: usize,
-    ) {
-        let mut f_xy = 0.0;
-        for v in 0..y_length {
-            for u in 0..x_length {
-                let f_uv = dct_block[v + y_start][u + x_start][c];
-                let c_uv: f64 = match (u, v) {
-                    (0, 0) => 1.0 / 2.0,
-                    (0, _) => ((1.0 / 2.0) as f64).sqrt(),
-                    (_, 0) => ((1.0 / 2.0) as f64).sqrt(),
-                    _ => 1.0,
-                };
-                f_xy += (c_uv as f64)
-                    * f_uv
-                    * ((((2 * x + 1) * u) as f64) * std::f64::consts::PI / 16.0).cos()
-                    * ((((2 * y + 1) * v) as f64) * std::f64::consts::PI / 16.0).cos();
+        pub fn channels(&self) -> usize {
+            match *self {
+                Order::Gray => 1,
+                _ => 3,
             }
         }
 
-        f_xy *= 1.0 / 4.0;
-
-        f_xy = f_xy.round();
+        // where a channel is stored among the channels of a pixel
+        fn position(&self, c: &Channel) -> usize {
+            match *self {
+                Order::Rgb => c.to_number(),
+                Order::Bgr => 2 - c.to_number(),
+                Order::Gray => 0,
+            }
+        }
+    }
 
-        block[y + y_start][x + x_start][c] = if f_xy < 0.0 {
-            0
-        } else if f_xy > 255.0 {
-            255
-        } else {
-            f_xy as u8
-        };
+    // how the samples of a headerless file are laid out, planar stores every channel after
+    // the other (RRR..GGG..BBB..), interleaved stores the channels of a pixel together
+    #[derive(Clone, Copy, Debug, PartialEq)]
+    pub struct RawLayout {
+        pub interleaved: bool,
+        pub depth: usize, // 8 or 16 bits per sample
+        pub big_endian: bool,
+        pub order: Order,
     }
 
-    pub fn dct_encode_block(
-        block: &[Vec<Pixel<u8>>],
-        frequencies: &mut Vec<Vec<Pixel<f64>>>,
-        number: usize,
-        block_size: usize,
-        x_start: usize,
-        x_length: usize,
-        y_start: usize,
-        y_length: usize,
-    ) {
-        let mut zigzag = Zigzag::new(block_size * block_size, block_size, block_size);
-        for (u, v) in zigzag.by_ref().take(number) {
-            for c in Channel::iterator() {
-                dct(
-                    block,
-                    frequencies,
-                    u,
-                    v,
-                    c,
-                    x_start,
-                    x_length,
-                    y_start,
-                    y_length,
-                );
-            }
-        }
-        for (u, v) in zigzag.by_ref() {
-            for c in Channel::iterator() {
-                frequencies[v + y_start][u + x_start][c] = 0.0;
+    impl Default for RawLayout {
+        // the planar 8 bit RGB of the course assignment
+        fn default() -> Self {
+            Self {
+                interleaved: false,
+                depth: 8,
+                big_endian: true,
+                order: Order::Rgb,
             }
         }
     }
 
-    pub fn dct_decode_block(
-        block: &mut Vec<Vec<Pixel<u8>>>,
-        frequencies: &[Vec<Pixel<f64>>],
-        x_start: usize,
-        x_length: usize,
-        y_start: usize,
-        y_length: usize,
-    ) {
-        for y in 0..y_length {
-            for x in 0..x_length {
-                for c in Channel::iterator() {
-                    idct(
-                        block,
-                        frequencies,
-                        x,
-                        y,
-                        c,
-                        x_start,
-                        x_length,
-                        y_start,
-                        y_length,
-                    );
+    impl RawLayout {
+        pub fn bytes_per_pixel(&self) -> usize {
+            self.order.channels() * self.depth / 8
+        }
+
+        // the sample of channel c at (x, y) scaled to 8 bits
+        pub fn sample(
+            &self,
+            data: &[u8],
+            width: usize,
+            height: usize,
+            x: usize,
+            y: usize,
+            c: &Channel,
+        ) -> u8 {
+            let channels = self.order.channels();
+            let position = self.order.position(c);
+            let index = if self.interleaved {
+                (y * width + x) * channels + position
+            } else {
+                position * width * height + y * width + x
+            };
+
+            match self.depth {
+                16 => {
+                    let bytes = [data[2 * index], data[2 * index + 1]];
+                    let value = if self.big_endian {
+                        u16::from_be_bytes(bytes)
+                    } else {
+                        u16::from_le_bytes(bytes)
+                    };
+                    ((u32::from(value) * 255 + 32767) / 65535) as u8
                 }
+                _ => data[index],
             }
         }
     }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+
+        #[test]
+        fn planar_and_interleaved() {
+            let planar = [1, 2, 3, 4, 5, 6];
+            let layout = RawLayout::default();
+            assert_eq!(layout.sample(&planar, 2, 1, 1, 0, &Channel::R), 2);
+            assert_eq!(layout.sample(&planar, 2, 1, 0, 0, &Channel::B), 5);
+
+            let interleaved = RawLayout {
+                interleaved: true,
+                ..RawLayout::default()
+            };
+            assert_eq!(interleaved.sample(&planar, 2, 1, 1, 0, &Channel::R), 4);
+            assert_eq!(interleaved.sample(&planar, 2, 1, 0, 0, &Channel::B), 3);
+
+            let bgr = RawLayout {
+                interleaved: true,
+                order: Order::Bgr,
+                ..RawLayout::default()
+            };
+            assert_eq!(bgr.sample(&planar, 2, 1, 0, 0, &Channel::R), 3);
+            assert_eq!(bgr.sample(&planar, 2, 1, 1, 0, &Channel::B), 4);
+        }
+
+        #[test]
+        fn sixteen_bit_and_gray() {
+            let data = [0xff, 0xff, 0x80, 0x00];
+            let big = RawLayout {
+                depth: 16,
+                order: Order::Gray,
+                ..RawLayout::default()
+            };
+            assert_eq!(big.bytes_per_pixel(), 2);
+            assert_eq!(big.sample(&data, 2, 1, 0, 0, &Channel::G), 255);
+            assert_eq!(big.sample(&data, 2, 1, 1, 0, &Channel::B), 128);
+
+            let little = RawLayout {
+                big_endian: false,
+                ..big
+            };
+            assert_eq!(little.sample(&data, 2, 1, 1, 0, &Channel::R), 0);
+        }
+    }
 }
 
-mod dwt {
+mod color {
+
+    use core::slice::Iter;
 
     use super::channel::Channel;
     use super::pixel::Pixel;
-    use crate::zigzag::Zigzag;
 
-    /*Discrete Wavelet Transformation Implementation*/
+    /*Color Transformations*/
 
-    fn dwt(
-        dwt_block: &mut Vec<Vec<Pixel<f64>>>,
-        c: &Channel,
-        by_row: bool,
-        x_start: usize,
-        x_length: usize,
-        y_start: usize,
-        y_length: usize,
-    ) {
-        let mut tmp_block: Vec<Vec<f64>> = vec![vec![0.0; x_length]; y_length];
+    #[derive(Clone, Copy, Debug, PartialEq)]
+    pub enum ColorTransform {
+        Rgb, // R, G and B are coded as they are
+        Ict, // JPEG irreversible YCbCr
+        Rct, // JPEG2000 reversible integer YCbCr
+    }
 
-        if by_row {
-            for y in 0..y_length {
-                for x in 0..x_length / 2 {
-                    let pixel_1 = dwt_block[y + y_start][2 * x + x_start][c];
-                    let pixel_2 = dwt_block[y + y_start][2 * x + 1 + x_start][c];
+    impl ColorTransform {
+        pub fn name(&self) -> &'static str {
+            match *self {
+                ColorTransform::Rgb => "rgb",
+                ColorTransform::Ict => "ict",
+                ColorTransform::Rct => "rct",
+            }
+        }
 
-                    tmp_block[y][x] = (pixel_1 + pixel_2) as f64 / 2.0;
-                    tmp_block[y][x + x_length / 2] = (pixel_1 - pixel_2) as f64 / 2.0;
-                }
+        pub fn from_name(name: &str) -> Option<Self> {
+            Self::iterator().find(|t| t.name() == name).copied()
+        }
+
+        pub fn iterator() -> Iter<'static, ColorTransform> {
+            static TRANSFORMS: [ColorTransform; 3] = [
+                ColorTransform::Rgb,
+                ColorTransform::Ict,
+                ColorTransform::Rct,
+            ];
+            TRANSFORMS.iter()
+        }
+
+        // the components the transform produces
+        pub fn channels(&self) -> Iter<'static, Channel> {
+            match *self {
+                ColorTransform::Rgb => Channel::iterator(),
+                _ => Channel::ycbcr_iterator(),
             }
-        } else {
-            for x in 0..x_length {
-                for y in 0..y_length / 2 {
-                    let pixel_1 = dwt_block[2 * y + y_start][x + x_start][c];
-                    let pixel_2 = dwt_block[2 * y + 1 + y_start][x + x_start][c];
+        }
 
-                    tmp_block[y][x] = (pixel_1 + pixel_2) as f64 / 2.0;
-                    tmp_block[y + y_length / 2][x] = (pixel_1 - pixel_2) as f64 / 2.0;
+        // chroma is offset by 128 so every component is centered like an 8 bit sample
+        pub fn forward(&self, p: &Pixel<u8>) -> Pixel<f64> {
+            let (r, g, b) = (f64::from(p.r), f64::from(p.g), f64::from(p.b));
+            let mut q = Pixel {
+                r: 0.0,
+                g: 0.0,
+                b: 0.0,
+            };
+            match *self {
+                ColorTransform::Rgb => {
+                    q[&Channel::R] = r;
+                    q[&Channel::G] = g;
+                    q[&Channel::B] = b;
+                }
+                ColorTransform::Ict => {
+                    q[&Channel::Y] = 0.299 * r + 0.587 * g + 0.114 * b;
+                    q[&Channel::Cb] = -0.168_736 * r - 0.331_264 * g + 0.5 * b + 128.0;
+                    q[&Channel::Cr] = 0.5 * r - 0.418_688 * g - 0.081_312 * b + 128.0;
+                }
+                ColorTransform::Rct => {
+                    q[&Channel::Y] = ((r + 2.0 * g + b) / 4.0).floor();
+                    q[&Channel::Cb] = b - g + 128.0;
+                    q[&Channel::Cr] = r - g + 128.0;
                 }
             }
+            q
         }
 
-        for y in 0..y_length {
-            for x in 0..x_length {
-                dwt_block[y + y_start][x + x_start][c] = tmp_block[y][x];
+        pub fn inverse(&self, q: &Pixel<f64>) -> Pixel<u8> {
+            let (r, g, b) = match *self {
+                ColorTransform::Rgb => (q[&Channel::R], q[&Channel::G], q[&Channel::B]),
+                ColorTransform::Ict => {
+                    let (y, cb, cr) = (
+                        q[&Channel::Y],
+                        q[&Channel::Cb] - 128.0,
+                        q[&Channel::Cr] - 128.0,
+                    );
+                    (
+                        y + 1.402 * cr,
+                        y - 0.344_136 * cb - 0.714_136 * cr,
+                        y + 1.772 * cb,
+                    )
+                }
+                ColorTransform::Rct => {
+                    // the transform is integer to integer
+                    let (y, cb, cr) = (
+                        q[&Channel::Y].round(),
+                        (q[&Channel::Cb] - 128.0).round(),
+                        (q[&Channel::Cr] - 128.0).round(),
+                    );
+                    let g = y - ((cb + cr) / 4.0).floor();
+                    (cr + g, g, cb + g)
+                }
+            };
+            Pixel {
+                r: clamp(r),
+                g: clamp(g),
+                b: clamp(b),
             }
         }
     }
 
-    fn idwt(
-        dwt_block: &mut Vec<Vec<Pixel<f64>>>,
-        c: &Channel,
-        by_row: bool,
-        x_start: usize,
-        x_length: usize,
-        y_start: usize,
-        y_length: usize,
-    ) {
-        let mut tmp_block: Vec<Vec<f64>> = vec![vec![0.0; x_length]; y_length];
-
-        if by_row {
-            for y in 0..y_length {
-                for x in 0..x_length / 2 {
-                    let average = dwt_block[y + y_start][x + x_start][c];
-                    let difference = dwt_block[y + y_start][x + x_length / 2 + x_start][c];
-
-                    tmp_block[y][2 * x] = average + difference;
-                    tmp_block[y][2 * x + 1] = average - difference;
-                }
-            }
+    fn clamp(v: f64) -> u8 {
+        if v < 0.0 {
+            0
+        } else if v > 255.0 {
+            255
         } else {
-            for x in 0..x_length {
-                for y in 0..y_length / 2 {
-                    let average = dwt_block[y + y_start][x + x_start][c];
-                    let difference = dwt_block[y + y_length / 2 + y_start][x + x_start][c];
+            v.round() as u8
+        }
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
 
-                    tmp_block[2 * y][x] = average + difference;
-                    tmp_block[2 * y + 1][x] = average - difference;
+        fn test_pixels() -> Vec<Pixel<u8>> {
+            let mut pixels = vec![];
+            for r in (0..256).step_by(15) {
+                for g in (0..256).step_by(17) {
+                    for b in (0..256).step_by(51) {
+                        pixels.push(Pixel {
+                            r: r as u8,
+                            g: g as u8,
+                            b: b as u8,
+                        });
+                    }
                 }
             }
+            pixels
         }
 
-        for y in 0..y_length {
-            for x in 0..x_length {
-                dwt_block[y + y_start][x + x_start][c] = tmp_block[y][x];
+        #[test]
+        fn rct_is_reversible() {
+            for p in test_pixels() {
+                let q = ColorTransform::Rct.inverse(&ColorTransform::Rct.forward(&p));
+                assert_eq!((q.r, q.g, q.b), (p.r, p.g, p.b));
             }
         }
-    }
 
-    pub fn dwt_encode_block(
-        dwt_block: &mut Vec<Vec<Pixel<f64>>>,
-        number: usize,
-        c: &Channel,
-        x_start: usize,
-        x_length: usize,
-        y_start: usize,
-        y_length: usize,
-    ) {
-        let mut x_dwt_blocksize = x_length;
-        let mut y_dwt_blocksize = y_length;
-        let mut by_row = true;
-
-        while x_dwt_blocksize > 1 || y_dwt_blocksize > 1 {
-            dwt(
-                dwt_block,
-                c,
-                by_row,
-                x_start,
-                x_dwt_blocksize,
-                y_start,
-                y_dwt_blocksize,
-            );
-            if !by_row {
-                x_dwt_blocksize /= 2;
-                y_dwt_blocksize /= 2;
+        #[test]
+        fn ict_round_trip() {
+            for p in test_pixels() {
+                let q = ColorTransform::Ict.inverse(&ColorTransform::Ict.forward(&p));
+                assert!((i16::from(q.r) - i16::from(p.r)).abs() <= 1);
+                assert!((i16::from(q.g) - i16::from(p.g)).abs() <= 1);
+                assert!((i16::from(q.b) - i16::from(p.b)).abs() <= 1);
             }
-
-            by_row = !by_row;
         }
 
-        /*use coefficient in zigzag order*/
-
-        if number == 0 {
-            for y in y_start..y_start + y_length {
-                for x in x_start..x_start + x_length {
-                    dwt_block[y][x][c] = 0.0;
-                }
+        #[test]
+        fn gray_has_neutral_chroma() {
+            let gray = Pixel {
+                r: 90,
+                g: 90,
+                b: 90,
+            };
+            for transform in &[ColorTransform::Ict, ColorTransform::Rct] {
+                let q = transform.forward(&gray);
+                assert!((q[&Channel::Y] - 90.0).abs() < 1e-9);
+                assert!((q[&Channel::Cb] - 128.0).abs() < 1e-9);
+                assert!((q[&Channel::Cr] - 128.0).abs() < 1e-9);
             }
-            return;
         }
+    }
+}
 
-        // TODO: change the axis using x_start and x_length
-        // calculate largest square
-        let mut counter: u32 = 0;
-        while number > (4_usize.pow(counter)) {
-            counter += 1;
-        }
-        if counter > 0 {
-            counter -= 1;
-        }
+mod subsampling {
 
-        let side_length: usize = 2_usize.pow(counter);
-        let area = side_length * side_length;
+    use core::slice::Iter;
 
-        // zero
-        if number > 3 * area {
-            let skip_number = number - 3 * area;
-            let zigzag = Zigzag::new(area, side_length, side_length);
-            for (x, y) in zigzag.skip(skip_number) {
-                dwt_block[y + side_length][x + side_length][c] = 0.0;
-            }
-        } else {
-            // let skip_number_right = (number - area) / 2;
-            // let skip_number_down = number - area - skip_number_right;
+    use super::plane::Plane;
 
-            let skip_number_down = std::cmp::min(number - area, area);
-            let skip_number_right = number - area - skip_number_down;
+    /*Chroma Subsampling*/
 
-            {
-                let zigzag = Zigzag::new(area, side_length, side_length);
-                for (x, y) in zigzag.skip(skip_number_right) {
-                    dwt_block[y][x + side_length][c] = 0.0;
-                }
-            }
-            {
-                let zigzag = Zigzag::new(area, side_length, side_length);
-                for (x, y) in zigzag.skip(skip_number_down) {
-                    dwt_block[y + side_length][x][c] = 0.0;
-                }
-            }
+    #[derive(Clone, Copy, Debug, PartialEq)]
+    pub enum Subsampling {
+        S444, // full resolution chroma
+        S422, // half horizontal resolution
+        S420, // half horizontal and vertical resolution
+    }
 
-            for y in side_length..2 * side_length {
-                for x in side_length..2 * side_length {
-                    dwt_block[y][x][c] = 0.0;
-                }
+    impl Subsampling {
+        pub fn name(&self) -> &'static str {
+            match *self {
+                Subsampling::S444 => "4:4:4",
+                Subsampling::S422 => "4:2:2",
+                Subsampling::S420 => "4:2:0",
             }
         }
 
-        // zero rest value
+        pub fn from_name(name: &str) -> Option<Self> {
+            Self::iterator()
+                .find(|s| s.name() == name || s.name().replace(':', "") == name)
+                .copied()
+        }
 
-        for y in 2 * side_length..y_length {
-            for x in 0..x_length {
-                dwt_block[y][x][c] = 0.0;
-            }
+        pub fn iterator() -> Iter<'static, Subsampling> {
+            static SUBSAMPLINGS: [Subsampling; 3] =
+                [Subsampling::S444, Subsampling::S422, Subsampling::S420];
+            SUBSAMPLINGS.iter()
         }
 
-        for y in 0..y_length {
-            for x in 2 * side_length..x_length {
-                dwt_block[y][x][c] = 0.0;
+        // (horizontal, vertical) chroma subsampling factors
+        pub fn factors(&self) -> (usize, usize) {
+            match *self {
+                Subsampling::S444 => (1, 1),
+                Subsampling::S422 => (2, 1),
+                Subsampling::S420 => (2, 2),
             }
         }
     }
 
-    pub fn dwt_decode_block(
-        dwt_block: &mut Vec<Vec<Pixel<f64>>>,
-        c: &Channel,
-        x_start: usize,
-        x_length: usize,
-        y_start: usize,
-        y_length: usize,
-    ) {
-        let mut x_dwt_blocksize = 1;
-        let mut y_dwt_blocksize = 1;
-        let mut by_row = false;
-
-        while x_dwt_blocksize < x_length || y_dwt_blocksize < y_length || by_row {
-            if !by_row {
-                x_dwt_blocksize *= 2;
-                y_dwt_blocksize *= 2;
-            }
-            idwt(
-                dwt_block,
-                c,
-                by_row,
-                x_start,
-                x_dwt_blocksize,
-                y_start,
-                y_dwt_blocksize,
-            );
-
-            by_row = !by_row;
-        }
+    #[derive(Clone, Copy, Debug, PartialEq)]
+    pub enum Filter {
+        Box,      // average down, replicate up
+        Bilinear, // triangle filter down, linear interpolation up
     }
-}
-
-#[derive(Clone)]
-pub struct Image {
-    width: usize,
-    height: usize,
-    coefficient: usize,
-    blocksize: usize,
-    pixels: Option<Vec<Vec<Pixel<u8>>>>,
-    frequencies: Option<Vec<Vec<Pixel<f64>>>>,
-}
 
-impl Image {
-    pub fn get_coefficient(&self) -> usize {
-        self.coefficient
-    }
+    impl Filter {
+        pub fn name(&self) -> &'static str {
+            match *self {
+                Filter::Box => "box",
+                Filter::Bilinear => "bilinear",
+            }
+        }
 
-    pub fn set_coefficient(&mut self, coefficent: usize) -> &mut Self {
-        self.coefficient = coefficent;
-        self
-    }
-}
+        pub fn from_name(name: &str) -> Option<Self> {
+            Self::iterator().find(|f| f.name() == name).copied()
+        }
 
-impl Image {
-    pub fn new() -> Self {
-        Self {
-            width: 0,
-            height: 0,
-            coefficient: 0,
-            blocksize: 0,
-            pixels: None,
-            frequencies: None,
+        pub fn iterator() -> Iter<'static, Filter> {
+            static FILTERS: [Filter; 2] = [Filter::Box, Filter::Bilinear];
+            FILTERS.iter()
         }
-    }
 
-    pub fn new_from_rgb(
-        width: usize,
-        height: usize,
-        coefficient: usize,
-        blocksize: usize,
-        data: &[u8],
-    ) -> Result<Self, std::io::Error> {
-        let mut pixels: Vec<Vec<Pixel<u8>>> = vec![vec![Pixel { r: 0, g: 0, b: 0 }; width]; height];
-        for c in Channel::iterator() {
-            for y in 0..height {
-                for x in 0..width {
-                    let index = c.to_number() * width * height + y * width + x;
-                    pixels[y][x][c] = data[index];
-                }
+        // a line of length n down to ceil(n / factor) samples, every output sample is centered
+        // on the factor input samples it replaces
+        fn down(&self, line: &[f64], factor: usize) -> Vec<f64> {
+            let length = line.len().div_ceil(factor);
+            match *self {
+                Filter::Box => (0..length)
+                    .map(|i| {
+                        let group = &line[i * factor..std::cmp::min((i + 1) * factor, line.len())];
+                        group.iter().sum::<f64>() / group.len() as f64
+                    })
+                    .collect(),
+                Filter::Bilinear => (0..length)
+                    .map(|i| {
+                        let center = (i * factor) as f64 + (factor - 1) as f64 / 2.0;
+                        let first = (i * factor).saturating_sub(factor);
+                        let last = std::cmp::min((i + 2) * factor, line.len());
+                        let (mut sum, mut weights) = (0.0, 0.0);
+                        for (x, f_x) in line.iter().enumerate().take(last).skip(first) {
+                            let weight = 1.0 - (x as f64 - center).abs() / factor as f64;
+                            if weight > 0.0 {
+                                sum += weight * f_x;
+                                weights += weight;
+                            }
+                        }
+                        sum / weights
+                    })
+                    .collect(),
             }
         }
 
-        Ok(Self {
-            width,
-            height,
-            coefficient,
-            blocksize,
-            pixels: Some(pixels),
-            frequencies: None,
-        })
-    }
-
-    pub fn to_1d_vec(&self) -> Vec<u8> {
-        let mut ret: Vec<u8> = vec![0; self.width * self.height * 3];
-        let stride = self.width * 3;
-        let pixels = self
-            .pixels
-            .as_ref()
-            .expect("to_1d_vec, image pixel could not be empty");
-        for c in Channel::iterator() {
-            for y in 0..self.height {
-                for x in 0..self.width {
-                    ret[y * stride + 3 * x + c.to_number()] = pixels[y][x][c];
-                }
+        // a line back up to length samples
+        fn up(&self, line: &[f64], length: usize, factor: usize) -> Vec<f64> {
+            match *self {
+                Filter::Box => (0..length).map(|x| line[x / factor]).collect(),
+                Filter::Bilinear => (0..length)
+                    .map(|x| {
+                        let position = (x as f64 - (factor - 1) as f64 / 2.0) / factor as f64;
+                        let position = position.clamp(0.0, (line.len() - 1) as f64);
+                        let i = position.floor() as usize;
+                        let t = position - i as f64;
+                        let right = std::cmp::min(i + 1, line.len() - 1);
+                        (1.0 - t) * line[i] + t * line[right]
+                    })
+                    .collect(),
             }
         }
-        ret
     }
-}
 
-impl Image {
-    pub fn dct_encode(&mut self) {
-        let frequencies: Vec<Vec<Pixel<f64>>> = vec![
-            vec![
-                Pixel {
-                    r: 0.0,
-                    g: 0.0,
-                    b: 0.0
-                };
-                self.width
-            ];
-            self.height
-        ];
-        self.frequencies = Some(frequencies);
-
-        let number = (self.coefficient as f64
-            / ((self.width / self.blocksize) * (self.height / self.blocksize)) as f64)
-            .round() as usize;
-
-        for y_block in 0..self.height / self.blocksize {
-            for x_block in 0..self.width / self.blocksize {
-                dct::dct_encode_block(
-                    self.pixels
-                        .as_ref()
-                        .expect("encode, image pixel could not be empty"),
-                    self.frequencies.as_mut().unwrap(),
-                    number,
-                    self.blocksize,
-                    x_block * self.blocksize,
-                    self.blocksize,
-                    y_block * self.blocksize,
-                    self.blocksize,
-                );
+    pub fn downsample(
+        plane: &Plane,
+        (x_factor, y_factor): (usize, usize),
+        filter: Filter,
+    ) -> Plane {
+        let rows: Vec<Vec<f64>> = plane
+            .samples
+            .iter()
+            .map(|row| filter.down(row, x_factor))
+            .collect();
+        let mut downsampled = Plane::new(
+            plane.width.div_ceil(x_factor),
+            plane.height.div_ceil(y_factor),
+        );
+        for x in 0..downsampled.width {
+            let column: Vec<f64> = rows.iter().map(|row| row[x]).collect();
+            for (y, f_y) in filter.down(&column, y_factor).into_iter().enumerate() {
+                downsampled.samples[y][x] = f_y;
             }
         }
+        downsampled
     }
 
-    pub fn dct_decode(&mut self) {
-        let pixels: Vec<Vec<Pixel<u8>>> =
-            vec![vec![Pixel { r: 0, g: 0, b: 0 }; self.width]; self.height];
-        self.pixels = Some(pixels);
-
-        for y_block in 0..self.height / self.blocksize {
-            for x_block in 0..self.width / self.blocksize {
-                dct::dct_decode_block(
-                    self.pixels.as_mut().unwrap(),
-                    self.frequencies
-                        .as_ref()
-                        .expect("decode, image frequencies could not be empty"),
-                    x_block * self.blocksize,
-                    self.blocksize,
-                    y_block * self.blocksize,
-                    self.blocksize,
-                );
+    pub fn upsample(
+        plane: &Plane,
+        width: usize,
+        height: usize,
+        (x_factor, y_factor): (usize, usize),
+        filter: Filter,
+    ) -> Plane {
+        let rows: Vec<Vec<f64>> = plane
+            .samples
+            .iter()
+            .map(|row| filter.up(row, width, x_factor))
+            .collect();
+        let mut upsampled = Plane::new(width, height);
+        for x in 0..width {
+            let column: Vec<f64> = rows.iter().map(|row| row[x]).collect();
+            for (y, f_y) in filter.up(&column, height, y_factor).into_iter().enumerate() {
+                upsampled.samples[y][x] = f_y;
             }
         }
+        upsampled
     }
 
-    pub fn dwt_encode(&mut self) {
-        let frequencies = self
-            .pixels
-            .as_ref()
-            .unwrap()
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+
+        fn test_plane(width: usize, height: usize) -> Plane {
+            let mut plane = Plane::new(width, height);
+            for (y, row) in plane.samples.iter_mut().enumerate() {
+                for (x, f_xy) in row.iter_mut().enumerate() {
+                    *f_xy = ((x * 7 + y * 13) % 256) as f64;
+                }
+            }
+            plane
+        }
+
+        #[test]
+        fn plane_sizes() {
+            let plane = test_plane(9, 5);
+            for filter in Filter::iterator() {
+                for &(subsampling, width, height) in &[
+                    (Subsampling::S444, 9, 5),
+                    (Subsampling::S422, 5, 5),
+                    (Subsampling::S420, 5, 3),
+                ] {
+                    let down = downsample(&plane, subsampling.factors(), *filter);
+                    assert_eq!((down.width, down.height), (width, height));
+                    assert_eq!(down.samples.len(), height);
+                    assert!(down.samples.iter().all(|row| row.len() == width));
+
+                    let up = upsample(&down, 9, 5, subsampling.factors(), *filter);
+                    assert_eq!((up.width, up.height), (9, 5));
+                    assert!(up.samples.iter().all(|row| row.len() == 9));
+                }
+            }
+        }
+
+        #[test]
+        fn box_averages() {
+            let mut plane = Plane::new(3, 2);
+            plane.samples = vec![vec![1.0, 3.0, 10.0], vec![5.0, 7.0, 20.0]];
+            let down = downsample(&plane, (2, 2), Filter::Box);
+            assert_eq!(down.samples, vec![vec![4.0, 15.0]]);
+            let up = upsample(&down, 3, 2, (2, 2), Filter::Box);
+            assert_eq!(up.samples, vec![vec![4.0, 4.0, 15.0], vec![4.0, 4.0, 15.0]]);
+        }
+
+        #[test]
+        fn bilinear_weights() {
+            let mut plane = Plane::new(4, 1);
+            plane.samples = vec![vec![0.0, 8.0, 16.0, 8.0]];
+            let down = downsample(&plane, (2, 1), Filter::Bilinear);
+            // (3 * 0 + 3 * 8 + 16) / 7 at the clipped left edge, (8 + 3 * 16 + 3 * 8) / 7 right
+            assert!((down.samples[0][0] - 40.0 / 7.0).abs() < 1e-9);
+            assert!((down.samples[0][1] - 80.0 / 7.0).abs() < 1e-9);
+
+            let mut plane = Plane::new(2, 1);
+            plane.samples = vec![vec![0.0, 8.0]];
+            let up = upsample(&plane, 4, 1, (2, 1), Filter::Bilinear);
+            assert_eq!(up.samples[0], vec![0.0, 2.0, 6.0, 8.0]);
+        }
+
+        #[test]
+        fn constant_planes_are_kept() {
+            let mut plane = Plane::new(7, 5);
+            plane.samples = vec![vec![42.0; 7]; 5];
+            for filter in Filter::iterator() {
+                for subsampling in Subsampling::iterator() {
+                    let down = downsample(&plane, subsampling.factors(), *filter);
+                    let up = upsample(&down, 7, 5, subsampling.factors(), *filter);
+                    assert!(up.samples.iter().flatten().all(|f| (f - 42.0).abs() < 1e-9));
+                }
+            }
+        }
+
+        #[test]
+        fn subsampling_names() {
+            for subsampling in Subsampling::iterator() {
+                assert_eq!(
+                    Subsampling::from_name(subsampling.name()),
+                    Some(*subsampling)
+                );
+            }
+            assert_eq!(Subsampling::from_name("420"), Some(Subsampling::S420));
+            assert_eq!(Subsampling::from_name("4:1:1"), None);
+            assert_eq!(Filter::from_name("bilinear"), Some(Filter::Bilinear));
+        }
+    }
+}
+
+pub mod dct {
+
+    use crate::zigzag::Zigzag;
+
+    use std::collections::HashMap;
+
+    /*Discrete Cosine Transformation Implementation*/
+
+    // quantized blocks are level shifted to be centered on zero as in JPEG, so that the DC step
+    // size works the same for dark and bright blocks; unquantized blocks are transformed as they
+    // are, like the DWT, and a block without coefficients decodes to 0
+    pub const LEVEL_SHIFT: f64 = 128.0;
+
+    pub fn level(quantized: bool) -> f64 {
+        if quantized {
+            LEVEL_SHIFT
+        } else {
+            0.0
+        }
+    }
+
+    // orthonormal basis table for an N-point transform, table[u][x] = α(u) * cos((2x + 1)uπ / 2N)
+    // with α(0) = sqrt(1 / N) and α(u) = sqrt(2 / N), so that the 2D transform of any N x M block
+    // is a row pass followed by a column pass
+    fn cosine_table(length: usize) -> Vec<Vec<f64>> {
+        let n = length as f64;
+        (0..length)
+            .map(|u| {
+                let alpha_u = if u == 0 {
+                    (1.0 / n).sqrt()
+                } else {
+                    (2.0 / n).sqrt()
+                };
+                (0..length)
+                    .map(|x| {
+                        alpha_u
+                            * ((((2 * x + 1) * u) as f64) * std::f64::consts::PI / (2.0 * n)).cos()
+                    })
+                    .collect()
+            })
+            .collect()
+    }
+
+    // the cosine tables of an x_length x y_length block and a buffer for the row pass, built
+    // once per block size and reused by every block of that size
+    pub struct Transform {
+        x_table: Vec<Vec<f64>>,
+        y_table: Vec<Vec<f64>>,
+        rows: Vec<f64>, // y_length rows of x_length values
+    }
+
+    impl Transform {
+        pub fn new(x_length: usize, y_length: usize) -> Self {
+            Self {
+                x_table: cosine_table(x_length),
+                y_table: cosine_table(y_length),
+                rows: vec![0.0; x_length * y_length],
+            }
+        }
+
+        pub fn size(&self) -> (usize, usize) {
+            (self.x_table.len(), self.y_table.len())
+        }
+
+        fn dct(
+            &mut self,
+            block: &[Vec<f64>],
+            dct_block: &mut [Vec<f64>],
+            x_start: usize,
+            y_start: usize,
+            level: f64,
+        ) {
+            let (x_length, _) = self.size();
+
+            // transform every row, rows[y][u]
+            for (y, tmp_row) in self.rows.chunks_mut(x_length).enumerate() {
+                let row = &block[y + y_start][x_start..x_start + x_length];
+                for (tmp, basis) in tmp_row.iter_mut().zip(self.x_table.iter()) {
+                    *tmp = row
+                        .iter()
+                        .zip(basis.iter())
+                        .map(|(f_xy, b)| (f_xy - level) * b)
+                        .sum();
+                }
+            }
+
+            // then every column, F(u, v)
+            for (v, basis) in self.y_table.iter().enumerate() {
+                for u in 0..x_length {
+                    let mut f_uv = 0.0;
+                    for (y, b) in basis.iter().enumerate() {
+                        f_uv += self.rows[y * x_length + u] * b;
+                    }
+                    dct_block[v + y_start][u + x_start] = f_uv;
+                }
+            }
+        }
+
+        fn idct(
+            &mut self,
+            block: &mut [Vec<f64>],
+            dct_block: &[Vec<f64>],
+            x_start: usize,
+            y_start: usize,
+            level: f64,
+        ) {
+            let (x_length, y_length) = self.size();
+
+            // inverse every row, rows[v][x]
+            for (v, tmp_row) in self.rows.chunks_mut(x_length).enumerate() {
+                tmp_row.iter_mut().for_each(|f_xy| *f_xy = 0.0);
+                let row = &dct_block[v + y_start][x_start..x_start + x_length];
+                for (&f_uv, basis) in row.iter().zip(self.x_table.iter()) {
+                    if f_uv == 0.0 {
+                        continue;
+                    }
+                    for (f_xy, b) in tmp_row.iter_mut().zip(basis.iter()) {
+                        *f_xy += f_uv * b;
+                    }
+                }
+            }
+
+            // then every column, f(x, y)
+            for y in 0..y_length {
+                for x in 0..x_length {
+                    let mut f_xy = 0.0;
+                    for (v, basis) in self.y_table.iter().enumerate() {
+                        f_xy += self.rows[v * x_length + x] * basis[y];
+                    }
+
+                    block[y + y_start][x + x_start] = f_xy + level;
+                }
+            }
+        }
+    }
+
+    // the transforms of every block size of a plane, the last row and column of blocks may be
+    // cut short
+    #[derive(Default)]
+    pub struct Transforms(HashMap<(usize, usize), Transform>);
+
+    impl Transforms {
+        pub fn new() -> Self {
+            Self::default()
+        }
+
+        pub fn get(&mut self, x_length: usize, y_length: usize) -> &mut Transform {
+            self.0
+                .entry((x_length, y_length))
+                .or_insert_with(|| Transform::new(x_length, y_length))
+        }
+    }
+
+    pub fn dct_encode_block(
+        transform: &mut Transform,
+        block: &[Vec<f64>],
+        frequencies: &mut [Vec<f64>],
+        number: usize,
+        steps: Option<&[Vec<f64>]>,
+        x_start: usize,
+        y_start: usize,
+    ) {
+        let (x_length, y_length) = transform.size();
+        transform.dct(block, frequencies, x_start, y_start, level(steps.is_some()));
+
+        truncate_block(frequencies, number, x_start, x_length, y_start, y_length);
+
+        /*quantize and keep the dequantized value so decoding is unchanged*/
+
+        if let Some(steps) = steps {
+            for v in 0..y_length {
+                for u in 0..x_length {
+                    let q = steps[v][u];
+                    let f_uv = &mut frequencies[v + y_start][u + x_start];
+                    *f_uv = (*f_uv / q).round() * q;
+                }
+            }
+        }
+    }
+
+    // keep the first number coefficients of the block in zigzag order
+    pub fn truncate_block(
+        frequencies: &mut [Vec<f64>],
+        number: usize,
+        x_start: usize,
+        x_length: usize,
+        y_start: usize,
+        y_length: usize,
+    ) {
+        let zigzag = Zigzag::new(x_length * y_length, x_length, y_length);
+        for (u, v) in zigzag.skip(number) {
+            frequencies[v + y_start][u + x_start] = 0.0;
+        }
+    }
+
+    // energy of the n-th coefficient of the block in zigzag order, i.e. what keeping one more
+    // coefficient would add
+    pub fn zigzag_energy(
+        frequencies: &[Vec<f64>],
+        n: usize,
+        x_start: usize,
+        x_length: usize,
+        y_start: usize,
+        y_length: usize,
+    ) -> f64 {
+        match Zigzag::new(x_length * y_length, x_length, y_length).nth(n) {
+            Some((u, v)) => {
+                let f_uv = frequencies[v + y_start][u + x_start];
+                f_uv * f_uv
+            }
+            None => 0.0,
+        }
+    }
+
+    // quantized blocks are shifted back by LEVEL_SHIFT
+    pub fn dct_decode_block(
+        transform: &mut Transform,
+        block: &mut [Vec<f64>],
+        frequencies: &[Vec<f64>],
+        quantized: bool,
+        x_start: usize,
+        y_start: usize,
+    ) {
+        transform.idct(block, frequencies, x_start, y_start, level(quantized));
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+
+        // the original per-coefficient definition, kept as the reference
+        fn dct_direct(
+            block: &[Vec<f64>],
+            dct_block: &mut [Vec<f64>],
+            u: usize,
+            v: usize,
+            x_start: usize,
+            y_start: usize,
+        ) {
+            let c_uv: f64 = match (u, v) {
+                (0, 0) => 1.0 / 2.0,
+                (0, _) => (1.0_f64 / 2.0).sqrt(),
+                (_, 0) => (1.0_f64 / 2.0).sqrt(),
+                _ => 1.0,
+            };
+
+            let mut f_uv = 0.0;
+            for y in 0..8 {
+                for x in 0..8 {
+                    let f_xy = block[y + y_start][x + x_start];
+
+                    f_uv += f_xy
+                        * ((((2 * x + 1) * u) as f64) * std::f64::consts::PI / 16.0).cos()
+                        * ((((2 * y + 1) * v) as f64) * std::f64::consts::PI / 16.0).cos();
+                }
+            }
+
+            dct_block[v + y_start][u + x_start] = f_uv * 1.0 / 4.0 * c_uv;
+        }
+
+        fn test_samples(width: usize, height: usize) -> Vec<Vec<f64>> {
+            (0..height)
+                .map(|y| {
+                    (0..width)
+                        .map(|x| ((x * 7 + y * 13 + (x ^ y) * 3) % 256) as f64)
+                        .collect()
+                })
+                .collect()
+        }
+
+        #[test]
+        fn separable_matches_direct() {
+            let block = test_samples(16, 16);
+            let mut separable = vec![vec![0.0; 16]; 16];
+            let mut direct = vec![vec![0.0; 16]; 16];
+
+            let mut transform = Transform::new(8, 8);
+            for &(x_start, y_start) in &[(0, 0), (8, 0), (0, 8), (8, 8)] {
+                dct_encode_block(
+                    &mut transform,
+                    &block,
+                    &mut separable,
+                    64,
+                    None,
+                    x_start,
+                    y_start,
+                );
+                for v in 0..8 {
+                    for u in 0..8 {
+                        dct_direct(&block, &mut direct, u, v, x_start, y_start);
+                    }
+                }
+            }
+
+            for y in 0..16 {
+                for x in 0..16 {
+                    assert!((separable[y][x] - direct[y][x]).abs() < 1e-9);
+                }
+            }
+        }
+
+        #[test]
+        fn round_trip_any_block_size() {
+            for &(width, height) in &[(8, 8), (4, 4), (16, 16), (32, 32), (16, 8), (5, 3)] {
+                let block = test_samples(width, height);
+                let mut frequencies = vec![vec![0.0; width]; height];
+                let mut decoded = vec![vec![0.0; width]; height];
+                let mut transform = Transform::new(width, height);
+
+                dct_encode_block(
+                    &mut transform,
+                    &block,
+                    &mut frequencies,
+                    width * height,
+                    None,
+                    0,
+                    0,
+                );
+                dct_decode_block(&mut transform, &mut decoded, &frequencies, false, 0, 0);
+
+                for y in 0..height {
+                    for x in 0..width {
+                        assert!((decoded[y][x] - block[y][x]).abs() < 1e-9);
+                    }
+                }
+            }
+        }
+
+        #[test]
+        fn constant_block_has_only_dc() {
+            let (width, height) = (16, 4);
+            let block = vec![vec![100.0; width]; height];
+            let mut frequencies = vec![vec![0.0; width]; height];
+
+            dct_encode_block(
+                &mut Transform::new(width, height),
+                &block,
+                &mut frequencies,
+                width * height,
+                None,
+                0,
+                0,
+            );
+
+            let dc = 100.0 * ((width * height) as f64).sqrt();
+            assert!((frequencies[0][0] - dc).abs() < 1e-9);
+            for (y, row) in frequencies.iter().enumerate() {
+                for (x, f) in row.iter().enumerate() {
+                    if (x, y) != (0, 0) {
+                        assert!(f.abs() < 1e-9);
+                    }
+                }
+            }
+
+            // quantized blocks are level shifted and shifted back
+            let steps = vec![vec![1.0; width]; height];
+            let mut transform = Transform::new(width, height);
+            let mut decoded = vec![vec![0.0; width]; height];
+            dct_encode_block(
+                &mut transform,
+                &block,
+                &mut frequencies,
+                width * height,
+                Some(&steps),
+                0,
+                0,
+            );
+            let dc = (100.0 - LEVEL_SHIFT) * ((width * height) as f64).sqrt();
+            assert!((frequencies[0][0] - dc).abs() < 1e-6);
+            dct_decode_block(&mut transform, &mut decoded, &frequencies, true, 0, 0);
+            assert!((decoded[3][7] - 100.0).abs() < 1e-6);
+        }
+
+        // cargo test bench_ -- --ignored --nocapture
+        #[test]
+        #[ignore]
+        fn bench_separable_against_direct() {
+            let size = 128;
+            let block = test_samples(size, size);
+            let mut frequencies = vec![vec![0.0; size]; size];
+
+            let now = std::time::Instant::now();
+            for y_block in 0..size / 8 {
+                for x_block in 0..size / 8 {
+                    for v in 0..8 {
+                        for u in 0..8 {
+                            dct_direct(&block, &mut frequencies, u, v, x_block * 8, y_block * 8);
+                        }
+                    }
+                }
+            }
+            let direct = now.elapsed();
+
+            let now = std::time::Instant::now();
+            let mut transform = Transform::new(8, 8);
+            for y_block in 0..size / 8 {
+                for x_block in 0..size / 8 {
+                    dct_encode_block(
+                        &mut transform,
+                        &block,
+                        &mut frequencies,
+                        64,
+                        None,
+                        x_block * 8,
+                        y_block * 8,
+                    );
+                }
+            }
+            let separable = now.elapsed();
+
+            println!(
+                "{}x{} dct, direct: {:?}, separable: {:?}",
+                size, size, direct, separable
+            );
+        }
+    }
+}
+
+pub mod dwt {
+
+    use crate::zigzag::Zigzag;
+
+    use core::slice::Iter;
+
+    /*Discrete Wavelet Transformation Implementation*/
+
+    #[derive(Clone, Copy, Debug, PartialEq)]
+    pub enum Wavelet {
+        Haar,
+        Cdf53,
+        Cdf97,
+    }
+
+    // CDF 9/7 lifting coefficients as used by JPEG2000
+    const ALPHA: f64 = -1.586_134_342_059_924;
+    const BETA: f64 = -0.052_980_118_572_961;
+    const GAMMA: f64 = 0.882_911_075_530_934;
+    const DELTA: f64 = 0.443_506_852_043_971;
+    const K: f64 = 1.230_174_104_914_001;
+
+    impl Wavelet {
+        pub fn name(&self) -> &'static str {
+            match *self {
+                Wavelet::Haar => "haar",
+                Wavelet::Cdf53 => "cdf53",
+                Wavelet::Cdf97 => "cdf97",
+            }
+        }
+
+        pub fn from_name(name: &str) -> Option<Self> {
+            Self::iterator().find(|w| w.name() == name).copied()
+        }
+
+        pub fn iterator() -> Iter<'static, Wavelet> {
+            static WAVELETS: [Wavelet; 3] = [Wavelet::Haar, Wavelet::Cdf53, Wavelet::Cdf97];
+            WAVELETS.iter()
+        }
+
+        // one level on a line, the result is laid out as [low.., high..] with ceil(n / 2) low
+        // and floor(n / 2) high coefficients
+        fn forward(&self, line: &mut [f64]) {
+            let (mut s, mut d) = split(line);
+            match *self {
+                Wavelet::Haar => {
+                    for (s_i, d_i) in s.iter_mut().zip(d.iter_mut()) {
+                        let (pixel_1, pixel_2) = (*s_i, *d_i);
+                        *s_i = (pixel_1 + pixel_2) / 2.0;
+                        *d_i = (pixel_1 - pixel_2) / 2.0;
+                    }
+                }
+                Wavelet::Cdf53 => {
+                    predict(&s, &mut d, |s_sum| -(s_sum / 2.0).floor());
+                    update(&mut s, &d, |d_sum| ((d_sum + 2.0) / 4.0).floor());
+                }
+                Wavelet::Cdf97 => {
+                    predict(&s, &mut d, |s_sum| ALPHA * s_sum);
+                    update(&mut s, &d, |d_sum| BETA * d_sum);
+                    predict(&s, &mut d, |s_sum| GAMMA * s_sum);
+                    update(&mut s, &d, |d_sum| DELTA * d_sum);
+                    s.iter_mut().for_each(|s_i| *s_i /= K);
+                    d.iter_mut().for_each(|d_i| *d_i *= K / 2.0);
+                }
+            }
+            line[..s.len()].copy_from_slice(&s);
+            line[s.len()..].copy_from_slice(&d);
+        }
+
+        fn inverse(&self, line: &mut [f64]) {
+            let (low, high) = line.split_at(line.len().div_ceil(2));
+            let (mut s, mut d) = (low.to_vec(), high.to_vec());
+            match *self {
+                Wavelet::Haar => {
+                    for (s_i, d_i) in s.iter_mut().zip(d.iter_mut()) {
+                        let (average, difference) = (*s_i, *d_i);
+                        *s_i = average + difference;
+                        *d_i = average - difference;
+                    }
+                }
+                Wavelet::Cdf53 => {
+                    update(&mut s, &d, |d_sum| -((d_sum + 2.0) / 4.0).floor());
+                    predict(&s, &mut d, |s_sum| (s_sum / 2.0).floor());
+                }
+                Wavelet::Cdf97 => {
+                    s.iter_mut().for_each(|s_i| *s_i *= K);
+                    d.iter_mut().for_each(|d_i| *d_i *= 2.0 / K);
+                    update(&mut s, &d, |d_sum| -DELTA * d_sum);
+                    predict(&s, &mut d, |s_sum| -GAMMA * s_sum);
+                    update(&mut s, &d, |d_sum| -BETA * d_sum);
+                    predict(&s, &mut d, |s_sum| -ALPHA * s_sum);
+                }
+            }
+            for (i, s_i) in s.iter().enumerate() {
+                line[2 * i] = *s_i;
+            }
+            for (i, d_i) in d.iter().enumerate() {
+                line[2 * i + 1] = *d_i;
+            }
+        }
+    }
+
+    // even and odd samples
+    fn split(line: &[f64]) -> (Vec<f64>, Vec<f64>) {
+        let s = line.iter().step_by(2).copied().collect();
+        let d = line.iter().skip(1).step_by(2).copied().collect();
+        (s, d)
+    }
+
+    // d[i] += step(s[i] + s[i + 1]) with symmetric extension at the right edge
+    fn predict<F: Fn(f64) -> f64>(s: &[f64], d: &mut [f64], step: F) {
+        for (i, d_i) in d.iter_mut().enumerate() {
+            let right = if i + 1 < s.len() { s[i + 1] } else { s[i] };
+            *d_i += step(s[i] + right);
+        }
+    }
+
+    // s[i] += step(d[i - 1] + d[i]) with symmetric extension at both edges
+    fn update<F: Fn(f64) -> f64>(s: &mut [f64], d: &[f64], step: F) {
+        if d.is_empty() {
+            return;
+        }
+        for (i, s_i) in s.iter_mut().enumerate() {
+            let left = if i > 0 { d[i - 1] } else { d[0] };
+            let right = if i < d.len() { d[i] } else { d[d.len() - 1] };
+            *s_i += step(left + right);
+        }
+    }
+
+    fn dwt(
+        dwt_block: &mut [Vec<f64>],
+        by_row: bool,
+        wavelet: Wavelet,
+        x_start: usize,
+        x_length: usize,
+        y_start: usize,
+        y_length: usize,
+    ) {
+        if by_row {
+            for row in dwt_block[y_start..y_start + y_length].iter_mut() {
+                wavelet.forward(&mut row[x_start..x_start + x_length]);
+            }
+        } else {
+            let mut line = vec![0.0; y_length];
+            let rows = &mut dwt_block[y_start..y_start + y_length];
+            for x in x_start..x_start + x_length {
+                for (l, row) in line.iter_mut().zip(rows.iter()) {
+                    *l = row[x];
+                }
+                wavelet.forward(&mut line);
+                for (l, row) in line.iter().zip(rows.iter_mut()) {
+                    row[x] = *l;
+                }
+            }
+        }
+    }
+
+    fn idwt(
+        dwt_block: &mut [Vec<f64>],
+        by_row: bool,
+        wavelet: Wavelet,
+        x_start: usize,
+        x_length: usize,
+        y_start: usize,
+        y_length: usize,
+    ) {
+        if by_row {
+            for row in dwt_block[y_start..y_start + y_length].iter_mut() {
+                wavelet.inverse(&mut row[x_start..x_start + x_length]);
+            }
+        } else {
+            let mut line = vec![0.0; y_length];
+            let rows = &mut dwt_block[y_start..y_start + y_length];
+            for x in x_start..x_start + x_length {
+                for (l, row) in line.iter_mut().zip(rows.iter()) {
+                    *l = row[x];
+                }
+                wavelet.inverse(&mut line);
+                for (l, row) in line.iter().zip(rows.iter_mut()) {
+                    row[x] = *l;
+                }
+            }
+        }
+    }
+
+    // (x_length, y_length) of the region transformed at each of at most `levels` levels, a side
+    // stops halving once it reaches 1 and odd sides keep the extra coefficient in the low band
+    fn level_sizes(x_length: usize, y_length: usize, levels: usize) -> Vec<(usize, usize)> {
+        let mut sizes = vec![];
+        let (mut x_dwt_blocksize, mut y_dwt_blocksize) = (x_length, y_length);
+        while (x_dwt_blocksize > 1 || y_dwt_blocksize > 1) && sizes.len() < levels {
+            sizes.push((x_dwt_blocksize, y_dwt_blocksize));
+            x_dwt_blocksize = x_dwt_blocksize.div_ceil(2);
+            y_dwt_blocksize = y_dwt_blocksize.div_ceil(2);
+        }
+        sizes
+    }
+
+    // (x, y, width, height) of every subband from the coarsest LL band to the finest HH band,
+    // each level is ordered LH (down), HL (right) then HH as in the zigzag selection
+    fn subbands(
+        x_length: usize,
+        y_length: usize,
+        levels: usize,
+    ) -> Vec<(usize, usize, usize, usize)> {
+        let sizes = level_sizes(x_length, y_length, levels);
+        let (x_low, y_low) = match sizes.last() {
+            Some(&(x, y)) => (x.div_ceil(2), y.div_ceil(2)),
+            None => (x_length, y_length),
+        };
+
+        let mut subbands = vec![(0, 0, x_low, y_low)];
+        for &(x_high, y_high) in sizes.iter().rev() {
+            let (x_low, y_low) = (x_high.div_ceil(2), y_high.div_ceil(2));
+            subbands.push((0, y_low, x_low, y_high - y_low));
+            subbands.push((x_low, 0, x_high - x_low, y_low));
+            subbands.push((x_low, y_low, x_high - x_low, y_high - y_low));
+        }
+        subbands
+            .into_iter()
+            .filter(|&(_, _, width, height)| width > 0 && height > 0)
+            .collect()
+    }
+
+    // transform the tile (x_start, x_length, y_start, y_length) of dwt_block, laid out as the
+    // pieces of split, and keep number of its coefficients
+    pub fn dwt_encode_block(
+        dwt_block: &mut [Vec<f64>],
+        number: usize,
+        wavelet: Wavelet,
+        levels: usize,
+        tile: (usize, usize, usize, usize),
+    ) {
+        let (x_start, x_length, y_start, y_length) = tile;
+        for (x_dwt_blocksize, y_dwt_blocksize) in level_sizes(x_length, y_length, levels) {
+            for &by_row in &[true, false] {
+                dwt(
+                    dwt_block,
+                    by_row,
+                    wavelet,
+                    x_start,
+                    x_dwt_blocksize,
+                    y_start,
+                    y_dwt_blocksize,
+                );
+            }
+        }
+
+        /*use coefficient in zigzag order*/
+
+        // fill the subbands from the coarsest one, zigzag within a subband
+        let mut rest = number;
+        for (x_band, y_band, width, height) in subbands(x_length, y_length, levels) {
+            let keep = std::cmp::min(rest, width * height);
+            rest -= keep;
+
+            let zigzag = Zigzag::new(width * height, width, height);
+            for (x, y) in zigzag.skip(keep) {
+                dwt_block[y + y_band + y_start][x + x_band + x_start] = 0.0;
+            }
+        }
+    }
+
+    pub fn dwt_decode_block(
+        dwt_block: &mut [Vec<f64>],
+        wavelet: Wavelet,
+        levels: usize,
+        tile: (usize, usize, usize, usize),
+    ) {
+        let (x_start, x_length, y_start, y_length) = tile;
+        let sizes = level_sizes(x_length, y_length, levels);
+        for (x_dwt_blocksize, y_dwt_blocksize) in sizes.into_iter().rev() {
+            for &by_row in &[false, true] {
+                idwt(
+                    dwt_block,
+                    by_row,
+                    wavelet,
+                    x_start,
+                    x_dwt_blocksize,
+                    y_start,
+                    y_dwt_blocksize,
+                );
+            }
+        }
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+
+        fn test_line(length: usize) -> Vec<f64> {
+            (0..length).map(|i| ((i * 37 + 11) % 256) as f64).collect()
+        }
+
+        #[test]
+        fn haar_average_difference() {
+            let mut line = vec![10.0, 4.0, 7.0, 9.0];
+            Wavelet::Haar.forward(&mut line);
+            assert_eq!(line, vec![7.0, 8.0, 3.0, -1.0]);
+            Wavelet::Haar.inverse(&mut line);
+            assert_eq!(line, vec![10.0, 4.0, 7.0, 9.0]);
+        }
+
+        #[test]
+        fn cdf53_is_integer_and_reversible() {
+            for &length in &[1, 2, 3, 8, 9, 17] {
+                let original = test_line(length);
+                let mut line = original.clone();
+                Wavelet::Cdf53.forward(&mut line);
+                assert!(line.iter().all(|v| *v == v.round()));
+                Wavelet::Cdf53.inverse(&mut line);
+                assert_eq!(line, original);
+            }
+        }
+
+        #[test]
+        fn cdf97_round_trip() {
+            for &length in &[1, 2, 3, 8, 9, 17] {
+                let original = test_line(length);
+                let mut line = original.clone();
+                Wavelet::Cdf97.forward(&mut line);
+                Wavelet::Cdf97.inverse(&mut line);
+                for (a, b) in line.iter().zip(original.iter()) {
+                    assert!((a - b).abs() < 1e-9);
+                }
+            }
+        }
+
+        #[test]
+        fn constant_line_has_no_detail() {
+            for wavelet in Wavelet::iterator() {
+                let mut line = vec![100.0; 16];
+                wavelet.forward(&mut line);
+                for (i, v) in line.iter().enumerate() {
+                    let expected = if i < 8 { 100.0 } else { 0.0 };
+                    assert!((v - expected).abs() < 1e-9, "{}", wavelet.name());
+                }
+            }
+        }
+
+        #[test]
+        fn subbands_cover_the_block() {
+            for &(width, height) in &[(1, 1), (8, 8), (5, 3), (1, 7), (513, 300)] {
+                let mut covered = vec![vec![0; width]; height];
+                for (x_band, y_band, w, h) in subbands(width, height, usize::MAX) {
+                    for row in covered[y_band..y_band + h].iter_mut() {
+                        for n in row[x_band..x_band + w].iter_mut() {
+                            *n += 1;
+                        }
+                    }
+                }
+                assert!(covered.iter().flatten().all(|&n| n == 1));
+            }
+
+            assert_eq!(
+                subbands(4, 4, 1),
+                vec![(0, 0, 2, 2), (0, 2, 2, 2), (2, 0, 2, 2), (2, 2, 2, 2)]
+            );
+            assert_eq!(
+                subbands(4, 4, 5),
+                vec![
+                    (0, 0, 1, 1),
+                    (0, 1, 1, 1),
+                    (1, 0, 1, 1),
+                    (1, 1, 1, 1),
+                    (0, 2, 2, 2),
+                    (2, 0, 2, 2),
+                    (2, 2, 2, 2),
+                ]
+            );
+        }
+
+        #[test]
+        fn wavelet_names() {
+            for wavelet in Wavelet::iterator() {
+                assert_eq!(Wavelet::from_name(wavelet.name()), Some(*wavelet));
+            }
+            assert_eq!(Wavelet::from_name("db4"), None);
+        }
+    }
+}
+
+mod selection {
+
+    use core::slice::Iter;
+
+    use super::plane::Plane;
+
+    /*Coefficient Selection Strategies*/
+
+    #[derive(Clone, Copy, Debug, PartialEq)]
+    pub enum Selection {
+        Zigzag,                // fixed order, zigzag within DCT blocks and DWT subbands
+        Largest,               // the largest magnitude coefficients of each channel
+        LargestAcrossChannels, // the largest magnitude coefficients of all channels together
+    }
+
+    impl Selection {
+        pub fn name(&self) -> &'static str {
+            match *self {
+                Selection::Zigzag => "zigzag",
+                Selection::Largest => "largest",
+                Selection::LargestAcrossChannels => "largest-across",
+            }
+        }
+
+        pub fn from_name(name: &str) -> Option<Self> {
+            Self::iterator().find(|s| s.name() == name).copied()
+        }
+
+        pub fn iterator() -> Iter<'static, Selection> {
+            static SELECTIONS: [Selection; 3] = [
+                Selection::Zigzag,
+                Selection::Largest,
+                Selection::LargestAcrossChannels,
+            ];
+            SELECTIONS.iter()
+        }
+    }
+
+    // zero everything but the numbers[i] largest magnitude coefficients of plane i, or the
+    // sum of numbers largest of all planes together
+    pub fn keep_largest(planes: &mut [Plane], numbers: &[usize], selection: Selection) {
+        match selection {
+            Selection::Zigzag => {}
+            Selection::Largest => {
+                for (plane, &number) in planes.iter_mut().zip(numbers) {
+                    keep(std::slice::from_mut(plane), number);
+                }
+            }
+            Selection::LargestAcrossChannels => {
+                keep(planes, numbers.iter().sum());
+            }
+        }
+    }
+
+    fn keep(planes: &mut [Plane], number: usize) {
+        let mut magnitudes: Vec<f64> = planes
             .iter()
-            .map(|row| {
-                row.iter()
-                    .map(|p| Pixel {
-                        r: f64::from(p.r),
-                        g: f64::from(p.g),
-                        b: f64::from(p.b),
-                    })
-                    .collect()
-            })
+            .flat_map(|plane| plane.samples.iter().flatten())
+            .map(|f| f.abs())
             .collect();
-        self.frequencies = Some(frequencies);
+        if number >= magnitudes.len() {
+            return;
+        }
+
+        // everything above the threshold is kept, ties on it are kept in scan order
+        let threshold = if number == 0 {
+            f64::INFINITY
+        } else {
+            let (_, nth, _) =
+                magnitudes.select_nth_unstable_by(number - 1, |a, b| b.partial_cmp(a).unwrap());
+            *nth
+        };
+        let mut ties = number - magnitudes.iter().filter(|m| **m > threshold).count();
+
+        for f in planes
+            .iter_mut()
+            .flat_map(|plane| plane.samples.iter_mut().flatten())
+        {
+            let magnitude = f.abs();
+            if magnitude > threshold {
+                continue;
+            }
+            if magnitude == threshold && ties > 0 {
+                ties -= 1;
+                continue;
+            }
+            *f = 0.0;
+        }
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+
+        fn planes(values: &[&[f64]]) -> Vec<Plane> {
+            values
+                .iter()
+                .map(|v| Plane {
+                    width: v.len(),
+                    height: 1,
+                    samples: vec![v.to_vec()],
+                })
+                .collect()
+        }
+
+        #[test]
+        fn largest_per_channel() {
+            let mut p = planes(&[&[1.0, -5.0, 4.0], &[-9.0, 2.0, 0.5], &[3.0, 3.0, 3.0]]);
+            keep_largest(&mut p, &[2, 2, 2], Selection::Largest);
+            assert_eq!(p[0].samples[0], vec![0.0, -5.0, 4.0]);
+            assert_eq!(p[1].samples[0], vec![-9.0, 2.0, 0.0]);
+            assert_eq!(p[2].samples[0], vec![3.0, 3.0, 0.0]);
+
+            // planes of different sizes get their own budget
+            let mut p = planes(&[&[1.0, 2.0, 3.0, 4.0], &[5.0, 6.0]]);
+            keep_largest(&mut p, &[3, 1], Selection::Largest);
+            assert_eq!(p[0].samples[0], vec![0.0, 2.0, 3.0, 4.0]);
+            assert_eq!(p[1].samples[0], vec![0.0, 6.0]);
+        }
+
+        #[test]
+        fn largest_across_channels() {
+            let mut p = planes(&[&[1.0, -5.0, 4.0], &[-9.0, 2.0, 0.5], &[3.0, 3.0, 8.0]]);
+            keep_largest(&mut p, &[1, 1, 1], Selection::LargestAcrossChannels);
+            let kept: Vec<f64> = p
+                .iter()
+                .flat_map(|plane| plane.samples[0].clone())
+                .filter(|v| *v != 0.0)
+                .collect();
+            assert_eq!(kept, vec![-5.0, -9.0, 8.0]);
+        }
+
+        #[test]
+        fn zigzag_and_large_budgets_keep_everything() {
+            let original = planes(&[&[1.0, 4.0], &[2.0, 5.0], &[3.0, 6.0]]);
+            for &(number, selection) in &[
+                (0, Selection::Zigzag),
+                (2, Selection::Largest),
+                (5, Selection::LargestAcrossChannels),
+            ] {
+                let mut p = original.clone();
+                keep_largest(&mut p, &[number; 3], selection);
+                assert!(p
+                    .iter()
+                    .zip(original.iter())
+                    .all(|(a, b)| a.samples == b.samples));
+            }
+
+            let mut p = original.clone();
+            keep_largest(&mut p, &[0; 3], Selection::Largest);
+            assert!(p
+                .iter()
+                .all(|plane| plane.samples[0].iter().all(|v| *v == 0.0)));
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct Image {
+    width: usize,
+    height: usize,
+    coefficient: usize,
+    selection: Selection,
+    color: ColorTransform,
+    subsampling: Subsampling,
+    filter: Filter,
+    pixels: Option<Vec<Vec<Pixel<u8>>>>,
+}
+
+impl Image {
+    pub fn get_size(&self) -> (usize, usize) {
+        (self.width, self.height)
+    }
+
+    pub fn get_coefficient(&self) -> usize {
+        self.coefficient
+    }
+
+    pub fn set_coefficient(&mut self, coefficent: usize) -> &mut Self {
+        self.coefficient = coefficent;
+        self
+    }
+
+    pub fn get_selection(&self) -> Selection {
+        self.selection
+    }
 
+    pub fn set_selection(&mut self, selection: Selection) -> &mut Self {
+        self.selection = selection;
+        self
+    }
+
+    pub fn get_color(&self) -> ColorTransform {
+        self.color
+    }
+
+    // color transform applied before encoding and inverted after decoding
+    pub fn set_color(&mut self, color: ColorTransform) -> &mut Self {
+        self.color = color;
+        self
+    }
+
+    pub fn get_subsampling(&self) -> Subsampling {
+        self.subsampling
+    }
+
+    // chroma subsampling, only the Cb and Cr planes of a YCbCr color transform are subsampled
+    pub fn set_subsampling(&mut self, subsampling: Subsampling) -> &mut Self {
+        self.subsampling = subsampling;
+        self
+    }
+
+    pub fn get_filter(&self) -> Filter {
+        self.filter
+    }
+
+    // filter used to downsample chroma before encoding and upsample it after decoding
+    pub fn set_filter(&mut self, filter: Filter) -> &mut Self {
+        self.filter = filter;
+        self
+    }
+
+    // names of the coded components, indexed like Coefficients::kept_coefficients
+    pub fn channel_names(&self) -> [&'static str; 3] {
+        let mut names = [""; 3];
+        for c in self.color.channels() {
+            names[c.to_number()] = c.name();
+        }
+        names
+    }
+}
+
+impl Default for Image {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Image {
+    pub fn new() -> Self {
+        Self {
+            width: 0,
+            height: 0,
+            coefficient: 0,
+            selection: Selection::Zigzag,
+            color: ColorTransform::Rgb,
+            subsampling: Subsampling::S444,
+            filter: Filter::Box,
+            pixels: None,
+        }
+    }
+
+    // planar 8 bit RGB data
+    pub fn new_from_rgb(
+        width: usize,
+        height: usize,
+        coefficient: usize,
+        data: &[u8],
+    ) -> Result<Self, Error> {
+        Self::new_from_raw(width, height, coefficient, data, &RawLayout::default())
+    }
+
+    pub fn new_from_raw(
+        width: usize,
+        height: usize,
+        coefficient: usize,
+        data: &[u8],
+        layout: &RawLayout,
+    ) -> Result<Self, Error> {
+        if width == 0 || height == 0 {
+            return Err(Error::InvalidDimensions(format!(
+                "image width and height must be at least 1, got {}x{}",
+                width, height
+            )));
+        }
+        if layout.depth != 8 && layout.depth != 16 {
+            return Err(Error::UnsupportedLayout(format!(
+                "raw samples must be 8 or 16 bits, got {}",
+                layout.depth
+            )));
+        }
+        // sizes no buffer can hold are refused instead of overflowing
+        let length = width
+            .checked_mul(height)
+            .and_then(|area| area.checked_mul(layout.bytes_per_pixel()))
+            .ok_or_else(|| {
+                Error::InvalidDimensions(format!("the {}x{} image is too large", width, height))
+            })?;
+        if data.len() != length {
+            return Err(Error::ShortInput {
+                width,
+                height,
+                expected: length,
+                actual: data.len(),
+            });
+        }
+
+        let mut pixels: Vec<Vec<Pixel<u8>>> = vec![vec![Pixel { r: 0, g: 0, b: 0 }; width]; height];
         for c in Channel::iterator() {
-            dwt::dwt_encode_block(
-                self.frequencies.as_mut().unwrap(),
-                self.coefficient,
-                c,
-                0,
-                self.width,
-                0,
-                self.height,
-            );
+            for (y, row) in pixels.iter_mut().enumerate() {
+                for (x, pixel) in row.iter_mut().enumerate() {
+                    pixel[c] = layout.sample(data, width, height, x, y, c);
+                }
+            }
+        }
+
+        Ok(Self {
+            width,
+            height,
+            coefficient,
+            selection: Selection::Zigzag,
+            color: ColorTransform::Rgb,
+            subsampling: Subsampling::S444,
+            filter: Filter::Box,
+            pixels: Some(pixels),
+        })
+    }
+
+    pub fn to_1d_vec(&self) -> Result<Vec<u8>, Error> {
+        let mut ret: Vec<u8> = vec![0; self.width * self.height * 3];
+        let stride = self.width * 3;
+        let pixels = self.pixels()?;
+        for c in Channel::iterator() {
+            for y in 0..self.height {
+                for x in 0..self.width {
+                    ret[y * stride + 3 * x + c.to_number()] = pixels[y][x][c];
+                }
+            }
         }
+        Ok(ret)
     }
 
-    pub fn dwt_decode(&mut self) {
+    // planar RGB, the layout new_from_rgb reads
+    pub fn to_planar_vec(&self) -> Result<Vec<u8>, Error> {
+        let mut ret: Vec<u8> = vec![0; self.width * self.height * 3];
+        let pixels = self.pixels()?;
         for c in Channel::iterator() {
-            dwt::dwt_decode_block(
-                self.frequencies
-                    .as_mut()
-                    .expect("decode, image frequencies could not be empty"),
-                c,
-                0,
-                self.width,
-                0,
-                self.height,
-            );
+            for y in 0..self.height {
+                for x in 0..self.width {
+                    ret[c.to_number() * self.width * self.height + y * self.width + x] =
+                        pixels[y][x][c];
+                }
+            }
         }
+        Ok(ret)
+    }
 
-        let pixels = self
-            .frequencies
-            .as_ref()
-            .unwrap()
-            .iter()
-            .map(|row| {
-                row.iter()
-                    .map(|p| {
-                        let r = if p.r < 0.0 {
-                            0
-                        } else if p.r > 255.0 {
-                            255
-                        } else {
-                            p.r.round() as u8
-                        };
+    // binary PPM (P6)
+    pub fn to_ppm(&self) -> Result<Vec<u8>, Error> {
+        let mut ret = format!("P6\n{} {}\n255\n", self.width, self.height).into_bytes();
+        ret.extend(self.to_1d_vec()?);
+        Ok(ret)
+    }
 
-                        let g = if p.g < 0.0 {
-                            0
-                        } else if p.g > 255.0 {
-                            255
-                        } else {
-                            p.g.round() as u8
-                        };
+    // an image made by Image::new has no pixels until it is read or decoded
+    fn pixels(&self) -> Result<&Vec<Vec<Pixel<u8>>>, Error> {
+        self.pixels.as_ref().ok_or_else(|| {
+            Error::InvalidDimensions(format!(
+                "the {}x{} image has no pixels",
+                self.width, self.height
+            ))
+        })
+    }
+}
 
-                        let b = if p.b < 0.0 {
-                            0
-                        } else if p.b > 255.0 {
-                            255
-                        } else {
-                            p.b.round() as u8
-                        };
+impl Image {
+    // mean squared error of the reconstruction against the original, per channel and overall
+    pub fn mse(&self, original: &Image) -> Result<Measure, Error> {
+        self.same_size(original)?;
+        let (a, b) = (self.to_planar_vec()?, original.to_planar_vec()?);
+        let area = self.width * self.height;
+        let mut channels = [0.0; 3];
+        for (mse, (a, b)) in channels.iter_mut().zip(a.chunks(area).zip(b.chunks(area))) {
+            *mse = metrics::mse(a, b);
+        }
+        Ok(Measure {
+            channels,
+            overall: metrics::mse(&a, &b),
+        })
+    }
+
+    pub fn psnr(&self, original: &Image) -> Result<Measure, Error> {
+        let mse = self.mse(original)?;
+        Ok(Measure {
+            channels: mse.channels.map(metrics::psnr),
+            overall: metrics::psnr(mse.overall),
+        })
+    }
+
+    // SSIM of every channel against the original, overall is the mean of the channels
+    pub fn ssim(&self, original: &Image) -> Result<Measure, Error> {
+        self.per_channel(original, metrics::ssim)
+    }
+
+    // multi-scale SSIM of every channel against the original, overall is the mean of the channels
+    pub fn ms_ssim(&self, original: &Image) -> Result<Measure, Error> {
+        self.per_channel(original, metrics::ms_ssim)
+    }
+
+    // blockiness of every channel on a grid of block_x x block_y blocks, usually the DCT ones
+    // whichever transform made the image, overall is the mean of the channels
+    pub fn blockiness(&self, block_x: usize, block_y: usize) -> Result<Measure, Error> {
+        self.per_channel(self, |a, _, width, height| {
+            metrics::blockiness(a, width, height, block_x, block_y)
+        })
+    }
+
+    // ringing of every channel next to the edges of the original, overall is the mean of the
+    // channels
+    pub fn ringing(&self, original: &Image) -> Result<Measure, Error> {
+        self.per_channel(original, |a, b, width, height| {
+            metrics::ringing(b, a, width, height)
+        })
+    }
+
+    fn per_channel<F>(&self, original: &Image, metric: F) -> Result<Measure, Error>
+    where
+        F: Fn(&[u8], &[u8], usize, usize) -> f64,
+    {
+        self.same_size(original)?;
+        let (a, b) = (self.to_planar_vec()?, original.to_planar_vec()?);
+        let area = self.width * self.height;
+        let mut channels = [0.0; 3];
+        for (value, (a, b)) in channels.iter_mut().zip(a.chunks(area).zip(b.chunks(area))) {
+            *value = metric(a, b, self.width, self.height);
+        }
+        Ok(Measure {
+            channels,
+            overall: channels.iter().sum::<f64>() / 3.0,
+        })
+    }
+
+    fn same_size(&self, original: &Image) -> Result<(), Error> {
+        if (self.width, self.height) != (original.width, original.height) {
+            return Err(Error::InvalidDimensions(format!(
+                "a {}x{} image cannot be compared with a {}x{} one",
+                self.width, self.height, original.width, original.height
+            )));
+        }
+        Ok(())
+    }
+}
+
+impl Image {
+    #[cfg(feature = "gui")]
+    pub fn save_png(&self, path: &std::path::Path) -> Result<(), Error> {
+        let pixbuf = gdk_pixbuf::Pixbuf::new_from_mut_slice(
+            self.to_1d_vec()?,
+            gdk_pixbuf::Colorspace::Rgb,
+            false,
+            8,
+            self.width as i32,
+            self.height as i32,
+            self.width as i32 * 3,
+        );
+        pixbuf
+            .savev(path, "png", &[])
+            .map_err(|err| Error::Io(std::io::Error::other(err.to_string())))
+    }
+
+    #[cfg(not(feature = "gui"))]
+    pub fn save_png(&self, _path: &std::path::Path) -> Result<(), Error> {
+        Err(Error::Io(std::io::Error::other(
+            "writing PNG images needs the gui feature",
+        )))
+    }
+
+    pub fn save_ppm(&self, path: &std::path::Path) -> Result<(), Error> {
+        Ok(std::fs::write(path, self.to_ppm()?)?)
+    }
+
+    pub fn save_raw(&self, path: &std::path::Path) -> Result<(), Error> {
+        Ok(std::fs::write(path, self.to_planar_vec()?)?)
+    }
+
+    // the format follows the extension, png, ppm and pnm, or rgb and raw for planar raw RGB
+    pub fn save(&self, path: &std::path::Path) -> Result<(), Error> {
+        let extension = path
+            .extension()
+            .map(|e| e.to_string_lossy().to_lowercase())
+            .unwrap_or_default();
+        match extension.as_str() {
+            "png" => self.save_png(path),
+            "ppm" | "pnm" => self.save_ppm(path),
+            "rgb" | "raw" => self.save_raw(path),
+            _ => Err(Error::UnsupportedLayout(format!(
+                "cannot save {}, use a png, ppm, pnm, rgb or raw extension",
+                path.display()
+            ))),
+        }
+    }
+}
+
+impl Image {
+    // the pixels after the color transform, one plane per component with the chroma planes
+    // downsampled
+    pub(crate) fn planes(&self) -> Result<Vec<Plane>, Error> {
+        let pixels = self.pixels()?;
+        let mut planes = vec![Plane::new(self.width, self.height); 3];
+        for (y, row) in pixels.iter().enumerate() {
+            for (x, p) in row.iter().enumerate() {
+                let q = self.color.forward(p);
+                for c in self.color.channels() {
+                    planes[c.to_number()].samples[y][x] = q[c];
+                }
+            }
+        }
+
+        for c in self.color.channels() {
+            let factors = self.factors(c);
+            if factors != (1, 1) {
+                let plane = &mut planes[c.to_number()];
+                *plane = subsampling::downsample(plane, factors, self.filter);
+            }
+        }
+        Ok(planes)
+    }
+
+    // upsample the chroma planes and invert the color transform, rounding and clamping to 8
+    // bit pixels
+    pub(crate) fn set_planes(&mut self, planes: &[Plane]) {
+        let planes: Vec<Plane> = self
+            .color
+            .channels()
+            .map(|c| {
+                let plane = &planes[c.to_number()];
+                let factors = self.factors(c);
+                if factors == (1, 1) {
+                    plane.clone()
+                } else {
+                    subsampling::upsample(plane, self.width, self.height, factors, self.filter)
+                }
+            })
+            .collect();
 
-                        Pixel { r, g, b }
+        let pixels = (0..self.height)
+            .map(|y| {
+                (0..self.width)
+                    .map(|x| {
+                        let q = Pixel {
+                            r: planes[0].samples[y][x],
+                            g: planes[1].samples[y][x],
+                            b: planes[2].samples[y][x],
+                        };
+                        self.color.inverse(&q)
                     })
                     .collect()
             })
             .collect();
         self.pixels = Some(pixels);
     }
+
+    // the settings of the image without its pixels, what decoding needs besides the
+    // coefficients
+    pub(crate) fn without_pixels(&self) -> Image {
+        Image {
+            width: self.width,
+            height: self.height,
+            coefficient: self.coefficient,
+            selection: self.selection,
+            color: self.color,
+            subsampling: self.subsampling,
+            filter: self.filter,
+            pixels: None,
+        }
+    }
+
+    // coefficients kept in every plane, the budget of the three channels is shared by plane
+    // area so that subsampled chroma leaves more of it to luma
+    pub(crate) fn plane_numbers(&self) -> Vec<usize> {
+        let areas: Vec<usize> = self
+            .color
+            .channels()
+            .map(|c| {
+                let (width, height) = self.plane_size(c);
+                width * height
+            })
+            .collect();
+        share(self.coefficient.saturating_mul(3), &areas)
+    }
+
+    // (horizontal, vertical) subsampling of a component
+    pub(crate) fn factors(&self, c: &Channel) -> (usize, usize) {
+        if c.is_chroma() {
+            self.subsampling.factors()
+        } else {
+            (1, 1)
+        }
+    }
+
+    pub(crate) fn plane_size(&self, c: &Channel) -> (usize, usize) {
+        let (x_factor, y_factor) = self.factors(c);
+        (
+            self.width.div_ceil(x_factor),
+            self.height.div_ceil(y_factor),
+        )
+    }
+}
+
+// (x_start, x_length, y_start, y_length) of every size_x x size_y piece of a width x height
+// image, the last row and column are cut short when the image is not a multiple of the size
+pub(crate) fn split(
+    width: usize,
+    height: usize,
+    size_x: usize,
+    size_y: usize,
+) -> Vec<(usize, usize, usize, usize)> {
+    let mut pieces = vec![];
+    for y_start in (0..height).step_by(size_y) {
+        for x_start in (0..width).step_by(size_x) {
+            pieces.push((
+                x_start,
+                std::cmp::min(size_x, width - x_start),
+                y_start,
+                std::cmp::min(size_y, height - y_start),
+            ));
+        }
+    }
+    pieces
+}
+
+// split number proportionally to areas so that the shares add up to exactly number (or to the
+// total area when number is larger), the largest remainders get the leftover
+pub(crate) fn share(number: usize, areas: &[usize]) -> Vec<usize> {
+    let total: usize = areas.iter().sum();
+    let number = std::cmp::min(number, total);
+    if total == 0 {
+        return vec![0; areas.len()];
+    }
+
+    let mut shares: Vec<usize> = areas.iter().map(|area| number * area / total).collect();
+    let mut rest = number - shares.iter().sum::<usize>();
+
+    let mut order: Vec<usize> = (0..areas.len()).collect();
+    order.sort_by_key(|&i| std::cmp::Reverse(number * areas[i] % total));
+    for i in order {
+        if rest == 0 {
+            break;
+        }
+        shares[i] += 1;
+        rest -= 1;
+    }
+    shares
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+
+    // planar RGB samples with some structure in both directions, shared by the tests of the crate
+    pub(crate) fn test_data(width: usize, height: usize) -> Vec<u8> {
+        (0..3 * width * height)
+            .map(|i| ((i * 37 + i / width * 11) % 256) as u8)
+            .collect()
+    }
+
+    #[test]
+    fn rgb_data_must_match_the_size() {
+        let data = test_data(20, 13);
+        assert!(Image::new_from_rgb(20, 13, 64, &data).is_ok());
+        assert!(Image::new_from_rgb(13, 20, 64, &data).is_ok());
+        assert!(matches!(
+            Image::new_from_rgb(20, 14, 64, &data),
+            Err(Error::ShortInput {
+                expected: 840,
+                actual: 780,
+                ..
+            })
+        ));
+        assert!(Image::new_from_rgb(20, 12, 64, &data).is_err());
+        assert!(matches!(
+            Image::new_from_rgb(0, 13, 64, &data),
+            Err(Error::InvalidDimensions(_))
+        ));
+        assert!(Image::new_from_rgb(0, 0, 64, &[]).is_err());
+        assert!(matches!(
+            Image::new_from_rgb(99_999_999_999, 99_999_999_999, 64, &data),
+            Err(Error::InvalidDimensions(_))
+        ));
+        assert!(matches!(
+            Image::new_from_rgb(usize::MAX / 2, 2, 64, &data),
+            Err(Error::InvalidDimensions(_))
+        ));
+        let layout = RawLayout {
+            depth: 12,
+            ..RawLayout::default()
+        };
+        assert!(matches!(
+            Image::new_from_raw(20, 13, 64, &data, &layout),
+            Err(Error::UnsupportedLayout(_))
+        ));
+
+        let layout = RawLayout {
+            interleaved: true,
+            depth: 16,
+            big_endian: false,
+            order: Order::Bgr,
+        };
+        let mut raw = vec![];
+        for &d in &data {
+            raw.extend_from_slice(&(u16::from(d) * 257).to_le_bytes());
+        }
+        let image = Image::new_from_raw(20, 13, 64, &raw, &layout).unwrap();
+        let rgb = image.to_1d_vec().unwrap();
+        assert_eq!(&rgb[..3], &[data[2], data[1], data[0]]);
+        assert!(Image::new_from_raw(20, 13, 64, &data, &layout).is_err());
+    }
+
+    #[test]
+    fn errors_instead_of_panics() {
+        let data = test_data(4, 2);
+        let image = Image::new_from_rgb(4, 2, 64, &data).unwrap();
+        let empty = Image::new();
+        assert!(matches!(
+            empty.to_1d_vec(),
+            Err(Error::InvalidDimensions(_))
+        ));
+        assert!(matches!(empty.planes(), Err(Error::InvalidDimensions(_))));
+        assert!(matches!(
+            image.mse(&empty),
+            Err(Error::InvalidDimensions(_))
+        ));
+        let missing = std::env::temp_dir().join("no_such_directory").join("a.ppm");
+        assert!(matches!(image.save(&missing), Err(Error::Io(_))));
+    }
+
+    #[test]
+    fn save_ppm_and_raw() {
+        let (width, height) = (5, 3);
+        let data = test_data(width, height);
+        let image = Image::new_from_rgb(width, height, 64, &data).unwrap();
+        assert_eq!(image.to_planar_vec().unwrap(), data);
+
+        let ppm = image.to_ppm().unwrap();
+        assert!(ppm.starts_with(b"P6\n5 3\n255\n"));
+        assert_eq!(&ppm[11..], &image.to_1d_vec().unwrap()[..]);
+
+        let directory = std::env::temp_dir();
+        let raw = directory.join(format!("save_ppm_and_raw_{}.rgb", std::process::id()));
+        image.save(&raw).unwrap();
+        assert_eq!(std::fs::read(&raw).unwrap(), data);
+        std::fs::remove_file(&raw).unwrap();
+
+        let ppm_path = raw.with_extension("ppm");
+        image.save(&ppm_path).unwrap();
+        assert_eq!(std::fs::read(&ppm_path).unwrap(), ppm);
+        std::fs::remove_file(&ppm_path).unwrap();
+
+        // other extensions are refused rather than written as raw bytes
+        for extension in ["jpg", "bmp", ""].iter() {
+            let path = raw.with_extension(extension);
+            assert!(matches!(
+                image.save(&path),
+                Err(Error::UnsupportedLayout(_))
+            ));
+            assert!(!path.exists());
+        }
+    }
+
+    #[test]
+    fn mse_against_the_original() {
+        let (width, height) = (4, 2);
+        let data = test_data(width, height);
+        let original = Image::new_from_rgb(width, height, 64, &data).unwrap();
+        assert_eq!(original.mse(&original).unwrap().overall, 0.0);
+        assert_eq!(original.psnr(&original).unwrap().overall, f64::INFINITY);
+
+        // every green sample off by 2, one blue sample off by 4
+        let mut changed = data.clone();
+        for g in changed[8..16].iter_mut() {
+            *g = g.wrapping_add(2);
+        }
+        changed[16] = changed[16].wrapping_sub(4);
+        let image = Image::new_from_rgb(width, height, 64, &changed).unwrap();
+        let mse = image.mse(&original).unwrap();
+        assert_eq!(mse.channels, [0.0, 4.0, 2.0]);
+        assert_eq!(mse.overall, 2.0);
+        assert!((image.psnr(&original).unwrap().channels[1] - metrics::psnr(4.0)).abs() < 1e-12);
+
+        let ssim = image.ssim(&original).unwrap();
+        assert_eq!(ssim.channels[0], 1.0);
+        assert!(ssim.channels[1] < 1.0 && ssim.channels[2] < 1.0);
+        assert!(ssim.overall < 1.0);
+        assert_eq!(original.ms_ssim(&original).unwrap().overall, 1.0);
+        assert_eq!(original.ringing(&original).unwrap().overall, 0.0);
+    }
+
+    #[test]
+    fn share_is_exact() {
+        assert_eq!(share(10, &[4, 4, 4]), vec![4, 3, 3]);
+        assert_eq!(share(7, &[64, 64, 32]), vec![3, 3, 1]);
+        assert_eq!(share(1000, &[2, 3]), vec![2, 3]);
+        assert_eq!(share(0, &[4, 4]), vec![0, 0]);
+        assert_eq!(share(5, &[]), Vec::<usize>::new());
+    }
 }
@@ -0,0 +1,341 @@
+use std::io::{Error, ErrorKind};
+use std::path::Path;
+
+use crate::image::RawLayout;
+
+/*Input Formats*/
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Format {
+    Raw,    // planar RGB without a header, as in the course assignment
+    Netpbm, // PPM and PGM, read natively
+    Png,
+    Jpeg,
+    Bmp,
+}
+
+impl Format {
+    pub fn name(&self) -> &'static str {
+        match *self {
+            Format::Raw => "raw",
+            Format::Netpbm => "netpbm",
+            Format::Png => "png",
+            Format::Jpeg => "jpeg",
+            Format::Bmp => "bmp",
+        }
+    }
+
+    // raw when a size or layout is given, then by extension, only files of an unknown
+    // extension are told by magic bytes as raw samples may start with any of them
+    pub fn detect(path: &Path, content: &[u8], raw: bool) -> Self {
+        if raw {
+            return Format::Raw;
+        }
+
+        let extension = path
+            .extension()
+            .map(|e| e.to_string_lossy().to_lowercase())
+            .unwrap_or_default();
+        match extension.as_str() {
+            "rgb" | "raw" => return Format::Raw,
+            "ppm" | "pgm" | "pnm" => return Format::Netpbm,
+            "png" => return Format::Png,
+            "jpg" | "jpeg" => return Format::Jpeg,
+            "bmp" => return Format::Bmp,
+            _ => {}
+        }
+
+        match content {
+            [b'P', b'2', ..] | [b'P', b'3', ..] | [b'P', b'5', ..] | [b'P', b'6', ..] => {
+                Format::Netpbm
+            }
+            [0x89, b'P', b'N', b'G', ..] => Format::Png,
+            [0xff, 0xd8, 0xff, ..] => Format::Jpeg,
+            [b'B', b'M', ..] => Format::Bmp,
+            _ => Format::Raw,
+        }
+    }
+}
+
+// width, height, data and data layout of the image at path, a file is read as raw with the
+// given size and layout when either is given, even the default layout, every other format is
+// decoded to planar 8 bit RGB with grayscale spread to all three channels
+pub fn read(
+    path: &Path,
+    size: Option<(usize, usize)>,
+    layout: Option<&RawLayout>,
+) -> Result<(usize, usize, Vec<u8>, RawLayout), Error> {
+    let content = std::fs::read(path)?;
+    let raw = size.is_some() || layout.is_some();
+    let layout = layout.copied().unwrap_or_default();
+    let (width, height, data) = match Format::detect(path, &content, raw) {
+        Format::Raw => {
+            let (width, height) = match size {
+                Some(size) => size,
+                None => square_size(content.len(), layout.bytes_per_pixel()).ok_or_else(|| {
+                    Error::new(
+                        ErrorKind::InvalidData,
+                        format!(
+                            "{} bytes is not a square image, give the size with --size",
+                            content.len()
+                        ),
+                    )
+                })?,
+            };
+            return Ok((width, height, content, layout));
+        }
+        Format::Netpbm => read_netpbm(&content)?,
+        format => read_pixbuf(path, format)?,
+    };
+    Ok((width, height, data, RawLayout::default()))
+}
+
+// raw files have no header, without a size they are taken to be square
+fn square_size(length: usize, bytes_per_pixel: usize) -> Option<(usize, usize)> {
+    let side = ((length / bytes_per_pixel) as f64).sqrt().round() as usize;
+    if side > 0 && bytes_per_pixel * side * side == length {
+        Some((side, side))
+    } else {
+        None
+    }
+}
+
+/*PPM and PGM*/
+
+// binary (P5, P6) and plain (P2, P3) netpbm images with 8 or 16 bit samples
+fn read_netpbm(content: &[u8]) -> Result<(usize, usize, Vec<u8>), Error> {
+    let invalid = |message: &str| Error::new(ErrorKind::InvalidData, message.to_string());
+
+    let (channels, plain) = match content.get(..2) {
+        Some(b"P2") => (1, true),
+        Some(b"P3") => (3, true),
+        Some(b"P5") => (1, false),
+        Some(b"P6") => (3, false),
+        _ => return Err(invalid("not a PPM or PGM image")),
+    };
+    let mut position = 2;
+    let width = next_number(content, &mut position)?;
+    let height = next_number(content, &mut position)?;
+    let maxval = next_number(content, &mut position)?;
+    if width == 0 || height == 0 {
+        return Err(invalid("image width and height must be at least 1"));
+    }
+    if maxval == 0 || maxval > 65535 {
+        return Err(invalid("netpbm maxval must be between 1 and 65535"));
+    }
+
+    // sizes the header cannot hold are refused before anything is allocated
+    let too_large = || invalid("netpbm image is too large");
+    let area = width
+        .checked_mul(height)
+        .filter(|area| area.checked_mul(3).is_some())
+        .ok_or_else(too_large)?;
+    let count = area * channels;
+    let samples: Vec<usize> = if plain {
+        (0..count)
+            .map(|_| next_number(content, &mut position))
+            .collect::<Result<_, _>>()?
+    } else {
+        // a single whitespace character separates the header from the raster
+        position += 1;
+        let bytes = if maxval < 256 { 1 } else { 2 };
+        let end = count
+            .checked_mul(bytes)
+            .and_then(|length| length.checked_add(position))
+            .ok_or_else(too_large)?;
+        let raster = content
+            .get(position..end)
+            .ok_or_else(|| invalid("netpbm raster is shorter than its header says"))?;
+        raster
+            .chunks(bytes)
+            .map(|s| s.iter().fold(0, |sample, &b| sample << 8 | usize::from(b)))
+            .collect()
+    };
+    if samples.iter().any(|&s| s > maxval) {
+        return Err(invalid("netpbm sample is larger than maxval"));
+    }
+
+    let mut data = vec![0; 3 * area];
+    for (c, channel) in data.chunks_mut(area).enumerate() {
+        for (i, d) in channel.iter_mut().enumerate() {
+            let sample = samples[i * channels + c % channels];
+            *d = ((sample * 255 + maxval / 2) / maxval) as u8;
+        }
+    }
+    Ok((width, height, data))
+}
+
+// the next decimal number of the header or of a plain raster, skipping whitespace and
+// comments
+fn next_number(content: &[u8], position: &mut usize) -> Result<usize, Error> {
+    while let Some(&b) = content.get(*position) {
+        if b == b'#' {
+            while content.get(*position).is_some_and(|&b| b != b'\n') {
+                *position += 1;
+            }
+        } else if b.is_ascii_whitespace() {
+            *position += 1;
+        } else {
+            break;
+        }
+    }
+
+    let start = *position;
+    while content.get(*position).is_some_and(u8::is_ascii_digit) {
+        *position += 1;
+    }
+    std::str::from_utf8(&content[start..*position])
+        .unwrap()
+        .parse()
+        .map_err(|_| Error::new(ErrorKind::InvalidData, "not a valid netpbm number"))
+}
+
+/*PNG, JPEG and BMP through gdk-pixbuf*/
+
+#[cfg(feature = "gui")]
+fn read_pixbuf(path: &Path, format: Format) -> Result<(usize, usize, Vec<u8>), Error> {
+    let pixbuf = gdk_pixbuf::Pixbuf::new_from_file(path).map_err(|err| {
+        Error::new(
+            ErrorKind::InvalidData,
+            format!("not a valid {} image: {}", format.name(), err),
+        )
+    })?;
+    let width = pixbuf.get_width() as usize;
+    let height = pixbuf.get_height() as usize;
+    let rowstride = pixbuf.get_rowstride() as usize;
+    let channels = pixbuf.get_n_channels() as usize;
+
+    // the pixels are only borrowed while pixbuf is alive and nothing else writes to them
+    let pixels = unsafe { pixbuf.get_pixels() };
+
+    let mut data = vec![0; 3 * width * height];
+    for (c, channel) in data.chunks_mut(width * height).enumerate() {
+        for y in 0..height {
+            for x in 0..width {
+                channel[y * width + x] = pixels[y * rowstride + x * channels + c % channels];
+            }
+        }
+    }
+    Ok((width, height, data))
+}
+
+#[cfg(not(feature = "gui"))]
+fn read_pixbuf(_path: &Path, format: Format) -> Result<(usize, usize, Vec<u8>), Error> {
+    Err(Error::other(format!(
+        "reading {} images needs the gui feature",
+        format.name()
+    )))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn detect_formats() {
+        let path = Path::new("image");
+        assert_eq!(
+            Format::detect(path, b"P6\n1 1\n255\n", false),
+            Format::Netpbm
+        );
+        assert_eq!(Format::detect(path, b"\x89PNG\r\n", false), Format::Png);
+        assert_eq!(
+            Format::detect(path, b"\xff\xd8\xff\xe0", false),
+            Format::Jpeg
+        );
+        assert_eq!(Format::detect(path, b"BM", false), Format::Bmp);
+        assert_eq!(Format::detect(path, &[0; 12], false), Format::Raw);
+        assert_eq!(
+            Format::detect(Path::new("a.JPG"), &[0; 12], false),
+            Format::Jpeg
+        );
+        assert_eq!(Format::detect(Path::new("a"), &[], false), Format::Raw);
+
+        // raw samples may look like magic bytes
+        assert_eq!(
+            Format::detect(Path::new("a.rgb"), b"BM", false),
+            Format::Raw
+        );
+        assert_eq!(
+            Format::detect(Path::new("a.RAW"), b"P6", false),
+            Format::Raw
+        );
+        assert_eq!(Format::detect(path, b"BM", true), Format::Raw);
+        assert_eq!(Format::detect(Path::new("a.png"), b"P6", true), Format::Raw);
+    }
+
+    #[test]
+    fn binary_ppm_and_pgm() {
+        let mut ppm = b"P6\n# a comment\n2 1\n255\n".to_vec();
+        ppm.extend_from_slice(&[1, 2, 3, 4, 5, 6]);
+        assert_eq!(read_netpbm(&ppm).unwrap(), (2, 1, vec![1, 4, 2, 5, 3, 6]));
+
+        let mut pgm = b"P5 2 1 255 ".to_vec();
+        pgm.extend_from_slice(&[7, 9]);
+        assert_eq!(read_netpbm(&pgm).unwrap(), (2, 1, vec![7, 9, 7, 9, 7, 9]));
+
+        // 16 bit big endian samples are scaled down to 8 bits
+        let mut pgm = b"P5 1 1 65535\n".to_vec();
+        pgm.extend_from_slice(&[0x80, 0x00]);
+        assert_eq!(read_netpbm(&pgm).unwrap(), (1, 1, vec![128, 128, 128]));
+    }
+
+    #[test]
+    fn plain_ppm() {
+        let ppm = b"P3\n2 1 # size\n15\n0 15 0\n15 0 15\n";
+        assert_eq!(
+            read_netpbm(ppm).unwrap(),
+            (2, 1, vec![0, 255, 255, 0, 0, 255])
+        );
+    }
+
+    #[test]
+    fn broken_netpbm() {
+        assert!(read_netpbm(b"P6\n2 2\n255\n\x01\x02").is_err());
+        assert!(read_netpbm(b"P5\n0 2\n255\n").is_err());
+        assert!(read_netpbm(b"P2\n1 1\n10\n11\n").is_err());
+        assert!(read_netpbm(b"P4\n1 1\n").is_err());
+        assert!(read_netpbm(b"P5\n1").is_err());
+        // sizes that overflow are refused instead of panicking or allocating
+        assert!(read_netpbm(b"P6\n99999999999 99999999999\n255\n").is_err());
+        assert!(read_netpbm(b"P5\n4294967296 4294967296\n255\n").is_err());
+        assert!(read_netpbm(b"P3\n99999999999 99999999999\n255\n").is_err());
+    }
+
+    #[test]
+    fn raw_files_with_a_size() {
+        let path = std::env::temp_dir().join(format!("input_test_{}.rgb", std::process::id()));
+        // a 2x2 planar file whose red samples start like a BMP and a PPM
+        for first in [b"BM", b"P6"].iter() {
+            let mut content = first.to_vec();
+            content.extend_from_slice(&[1, 2, 3, 4, 5, 6, 7, 8, 9, 10]);
+            std::fs::write(&path, &content).unwrap();
+            let (width, height, data, layout) = read(&path, Some((2, 2)), None).unwrap();
+            assert_eq!(
+                (width, height, data, layout),
+                (2, 2, content, RawLayout::default())
+            );
+        }
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn default_layout_reads_raw() {
+        let path = std::env::temp_dir().join(format!("input_test_{}.pgm", std::process::id()));
+        // the 12 bytes of a 1x1 PGM, read as a 2x2 planar file when a layout is given
+        std::fs::write(&path, b"P5\n1 1\n255\n\0").unwrap();
+        let (width, height, _, _) = read(&path, None, None).unwrap();
+        assert_eq!((width, height), (1, 1));
+        let (width, height, data, _) = read(&path, None, Some(&RawLayout::default())).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!((width, height, &data[..3]), (2, 2, &b"P5\n"[..]));
+    }
+
+    #[test]
+    fn square_raw_size() {
+        assert_eq!(square_size(3 * 512 * 512, 3), Some((512, 512)));
+        assert_eq!(square_size(2 * 64 * 64, 2), Some((64, 64)));
+        assert_eq!(square_size(3 * 20 * 13, 3), None);
+        assert_eq!(square_size(0, 3), None);
+    }
+}
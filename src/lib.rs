@@ -0,0 +1,21 @@
+// DCT and DWT coding of RGB images, with the metrics and rate-distortion tables to compare
+// them; the window lives in the gui_dct_dwt binary behind the gui feature
+
+pub mod codec;
+pub mod config;
+pub mod error;
+pub mod headless;
+pub mod image;
+pub mod input;
+pub mod metrics;
+pub mod quantization;
+pub mod rd;
+pub mod zigzag;
+
+pub use self::codec::{Codec, Coefficients};
+pub use self::error::Error;
+pub use self::image::dct::{dct_decode_block, dct_encode_block};
+pub use self::image::dwt::{dwt_decode_block, dwt_encode_block};
+pub use self::image::Image;
+pub use self::metrics::Measure;
+pub use self::zigzag::Zigzag;
@@ -1,266 +1,295 @@
+#[cfg(feature = "gui")]
 extern crate gio;
+#[cfg(feature = "gui")]
 extern crate gtk;
 
-use gio::prelude::*;
-use gtk::prelude::*;
-
-mod config;
-mod image;
-mod zigzag;
-
-use self::config::Config;
-use self::image::Image;
-
-fn build_ui(application: &gtk::Application) {
-    let config = Config::new(std::env::args()).unwrap_or_else(|err| {
-        eprintln!("Problem parsing arguments: {}", err);
-        std::process::exit(1);
-    });
+use dct_dwt::config::{ArgsError, Config};
+use dct_dwt::error::Error;
+use dct_dwt::image::Image;
+use dct_dwt::{headless, input};
+
+// the input image with every option of the configuration applied
+fn load_image(config: &Config) -> Result<Image, Error> {
+    let (width, height, content, layout) =
+        input::read(&config.path, config.size, config.raw_layout.as_ref())?;
+
+    let mut image_rgb = Image::new_from_raw(
+        width,
+        height,
+        // the sweep sets the budget of every frame
+        config.coefficient.max(0) as usize,
+        &content,
+        &layout,
+    )?;
+    image_rgb
+        .set_selection(config.selection)
+        .set_color(config.color)
+        .set_subsampling(config.subsampling)
+        .set_filter(config.filter);
+    Ok(image_rgb)
+}
 
-    let content = std::fs::read(&config.path).expect("path illegal");
-    // let name = config.path.file_stem().expect("extract the stem of file_name failed").to_string_lossy().into_owned();
+// the window, builds without the gui feature only run --headless
+#[cfg(feature = "gui")]
+mod window {
+    use gio::prelude::*;
+    use gtk::prelude::*;
+
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    use dct_dwt::codec::{self, Codec, Coefficients};
+    use dct_dwt::config::Config;
+    use dct_dwt::error::Error;
+    use dct_dwt::image::Image;
+    use dct_dwt::rd::{self, Point};
+    use dct_dwt::Measure;
+
+    // nonzero coefficients actually kept per channel, the budget is an upper bound once
+    // quantization zeroes coefficients of its own, then the MSE, PSNR and SSIM of the
+    // reconstruction and its block and ringing artifacts
+    fn result_label(
+        coefficients: &Coefficients,
+        image: &Image,
+        original: &Image,
+        psnr: &Measure,
+        ssim: &Measure,
+        config: &Config,
+    ) -> Result<String, Error> {
+        let kept = coefficients.kept_coefficients();
+        let names = image.channel_names();
+        let mse = image.mse(original)?;
+        let ms_ssim = image.ms_ssim(original)?;
+        let blockiness = image.blockiness(config.blocksize_x, config.blocksize_y)?;
+        let ringing = image.ringing(original)?;
+        Ok(format!(
+            "kept {} {} / {} {} / {} {}\nMSE R {:.2} / G {:.2} / B {:.2} / all {:.2}\nPSNR R {:.2} / G {:.2} / B {:.2} / all {:.2} dB\nSSIM {:.4}, MS-SSIM {:.4}, blockiness {:.2}, ringing {:.2}",
+            names[0],
+            kept[0],
+            names[1],
+            kept[1],
+            names[2],
+            kept[2],
+            mse.channels[0],
+            mse.channels[1],
+            mse.channels[2],
+            mse.overall,
+            psnr.channels[0],
+            psnr.channels[1],
+            psnr.channels[2],
+            psnr.overall,
+            ssim.overall,
+            ms_ssim.overall,
+            blockiness.overall,
+            ringing.overall
+        ))
+    }
 
-    /*set up parameters*/
-    let width = 512;
-    let height = 512;
-    let blocksize = 8;
-    let coefficient = config.coefficient as usize;
+    // a reconstruction with its results
+    struct Frame {
+        image: Image,
+        result: String,
+        point: Point,
+    }
 
-    if config.coefficient == -1 {
-        let mut image_rgb =
-            Image::new_from_rgb(width, height, coefficient, blocksize, &content).unwrap();
+    // the reconstructions of a codec for every budget with their results, each worked out once as
+    // SSIM is too slow for the timeout and only when it is first needed
+    struct Series {
+        codec: Box<dyn Codec>,
+        tag: String,
+        config: Rc<Config>,
+        original: Rc<Image>,
+        budgets: Vec<usize>,
+        frames: Vec<Option<Frame>>,
+    }
 
-        let mut image_dct_series: Vec<Image> = vec![Image::new(); 64];
-        let mut image_dwt_series: Vec<Image> = vec![Image::new(); 64];
+    impl Series {
+        fn new(
+            codec: Box<dyn Codec>,
+            config: &Rc<Config>,
+            original: &Rc<Image>,
+        ) -> Result<Self, Error> {
+            // with bits per pixel targets every codec gets budgets of its own
+            let budgets = config.budgets(original, codec.as_ref())?;
+            Ok(Series {
+                tag: codec.tag(),
+                codec,
+                config: Rc::clone(config),
+                original: Rc::clone(original),
+                frames: budgets.iter().map(|_| None).collect(),
+                budgets,
+            })
+        }
 
-        let max_iteration = 64;
-        for i in 0..max_iteration {
-            println!("converting image iteration {} ..", i + 1);
+        fn frame(&mut self, index: usize) -> Result<&Frame, Error> {
+            if self.frames[index].is_none() {
+                let original = &self.original;
+                let (coefficients, image) =
+                    self.codec.reconstruct(original, self.budgets[index])?;
+                // PSNR and SSIM go both in the label and in the rate-distortion point
+                let (psnr, ssim) = (image.psnr(original)?, image.ssim(original)?);
+                self.frames[index] = Some(Frame {
+                    result: result_label(
+                        &coefficients,
+                        &image,
+                        original,
+                        &psnr,
+                        &ssim,
+                        &self.config,
+                    )?,
+                    point: Point::new(&self.tag, &coefficients, psnr.overall, ssim.overall),
+                    image,
+                });
+            }
+            Ok(self.frames[index].as_ref().unwrap())
+        }
+    }
 
-            let base_coefficient = 4096;
-            image_rgb.set_coefficient((i + 1) * base_coefficient);
+    // every frame of a codec up front, the rate-distortion table needs them all
+    fn encode_series(
+        codec: Box<dyn Codec>,
+        config: &Rc<Config>,
+        original: &Rc<Image>,
+    ) -> Result<Series, Error> {
+        let mut series = Series::new(codec, config, original)?;
+        for i in 0..series.budgets.len() {
+            println!(
+                "converting {} image iteration {} ..",
+                series.codec.name(),
+                i + 1
+            );
+            series.frame(i)?;
+        }
+        Ok(series)
+    }
 
-            image_dct_series[i] = image_rgb.clone();
-            image_dwt_series[i] = image_rgb.clone();
+    // one budget when every codec shares it, as with every sweep but bits per pixel targets
+    fn budget_label(series: &[Series], index: usize) -> String {
+        let budgets: Vec<usize> = series.iter().map(|series| series.budgets[index]).collect();
+        if budgets.iter().all(|&budget| budget == budgets[0]) {
+            budgets[0].to_string()
+        } else {
+            series
+                .iter()
+                .zip(&budgets)
+                .map(|(series, budget)| format!("{} {}", series.tag, budget))
+                .collect::<Vec<String>>()
+                .join(" / ")
+        }
+    }
 
-            /*encode using dct or dwt*/
-            image_dct_series[i].dct_encode();
-            image_dct_series[i].dct_decode();
+    // the widgets showing a frame, one image and result per codec
+    #[derive(Clone)]
+    struct Columns {
+        budget: gtk::Label,
+        images: Vec<gtk::Image>,
+        results: Vec<gtk::Label>,
+    }
 
-            image_dwt_series[i].dwt_encode();
-            image_dwt_series[i].dwt_decode();
+    fn show_frame(columns: &Columns, series: &mut [Series], index: usize) {
+        columns
+            .budget
+            .set_label(budget_label(series, index).as_str());
+        for (c, series) in series.iter_mut().enumerate() {
+            match series.frame(index) {
+                Ok(frame) => {
+                    show_image(&columns.images[c], &frame.image);
+                    columns.results[c].set_label(frame.result.as_str());
+                }
+                Err(err) => error_dialog(&format!("Problem encoding: {}", err)),
+            }
         }
+    }
 
-        let window = gtk::ApplicationWindow::new(application);
-
-        window.set_title("CSCI576 Assginment2");
-        window.set_border_width(10);
-        window.set_position(gtk::WindowPosition::Center);
-        window.set_default_size(1064, 630);
+    // the window stays usable when the rate-distortion table cannot be written
+    fn write_rd(config: &Config, name: &str, points: &[Point]) {
+        if let Err(err) = rd::write(config, name, points) {
+            eprintln!("Problem writing the rate-distortion table: {}", err);
+        }
+    }
 
-        let grid = gtk::Grid::new();
-        let button = gtk::Button::new_with_label("Pasue");
-        let button_clone = button.clone();
-        let button_2 = gtk::Button::new_with_label("Restart");
-        let label_0 = gtk::Label::new(image_dct_series[0].get_coefficient().to_string().as_str());
-        let label_0_clone = label_0.clone();
-        let label_0_clone_clone = label_0.clone();
-        let label1 = gtk::Label::new("dct");
-        let label2 = gtk::Label::new("dwt");
-
-        let image_width: i32 = width as i32;
-        let image_height: i32 = height as i32;
-
-        // let mut image_data_1:Vec<u8> = vec![0; (image_width*image_width*3) as usize];
-        let image_data_1 = image_dct_series[0].to_1d_vec();
-        let pixbuf_1 = gdk_pixbuf::Pixbuf::new_from_mut_slice(
-            image_data_1,
-            gdk_pixbuf::Colorspace::Rgb,
-            false,
-            8,
-            image_width,
-            image_height,
-            image_width * 3,
+    // errors in the window are shown instead of aborting the application
+    fn error_dialog(message: &str) {
+        let dialog = gtk::MessageDialog::new(
+            None::<&gtk::Window>,
+            gtk::DialogFlags::MODAL,
+            gtk::MessageType::Error,
+            gtk::ButtonsType::Close,
+            message,
         );
+        dialog.run();
+        dialog.destroy();
+    }
 
-        // let image_data_2:Vec<u8> = vec![255; (image_width*image_width*3) as usize];
-        let image_data_2 = image_dwt_series[0].to_1d_vec();
-        let pixbuf_2 = gdk_pixbuf::Pixbuf::new_from_mut_slice(
-            image_data_2,
+    fn pixbuf(image: &Image) -> Result<gdk_pixbuf::Pixbuf, Error> {
+        let (width, height) = image.get_size();
+        Ok(gdk_pixbuf::Pixbuf::new_from_mut_slice(
+            image.to_1d_vec()?,
             gdk_pixbuf::Colorspace::Rgb,
             false,
             8,
-            image_width,
-            image_height,
-            image_width * 3,
-        );
-
-        let image_1 = gtk::Image::new_from_pixbuf(&pixbuf_1);
-        let image_1_clone = image_1.clone();
-        let image_1_clone_clone = image_1.clone();
-        let image_2 = gtk::Image::new_from_pixbuf(&pixbuf_2);
-        let image_2_clone = image_2.clone();
-        let image_2_clone_clone = image_2.clone();
-
-        use std::sync::{Arc, Mutex};
-
-        let image_dct = Arc::new(Mutex::new(image_dct_series));
-        let image_dwt = Arc::new(Mutex::new(image_dwt_series));
-
-        let image_dct_clone = Arc::clone(&image_dct);
-        let image_dwt_clone = Arc::clone(&image_dwt);
-
-        let pause = Arc::new(Mutex::new(false));
-        let pause_clone = Arc::clone(&pause);
-        let pause_clone_1 = Arc::clone(&pause);
-
-        let interval = 800;
-        // let mut flag = false;
-        // let mut counter = 1;
-        let counter = Arc::new(Mutex::new(1));
-        let counter_clone = Arc::clone(&counter);
-        let counter_clone_1 = Arc::clone(&counter);
-        //  let mut barrier:usize = 0;
-        gtk::timeout_add(interval, move || {
-            // if barrier < 30 {
-            //     barrier += 1;
-            //     return gtk::Continue(true);
-            // } else {
-            //     barrier = 0;
-            // }
-
-            if *pause.lock().unwrap() {
-                return gtk::Continue(true);
-            }
-
-            let mut counter_ptr = counter_clone.lock().unwrap();
-
-            if *counter_ptr >= max_iteration {
-                return gtk::Continue(true);
-            }
-
-            let image_dct_vec = image_dct.lock().unwrap();
-            let image_dwt_vec = image_dwt.lock().unwrap();
-
-            let pixbuf_1 = gdk_pixbuf::Pixbuf::new_from_mut_slice(
-                (*image_dct_vec)[*counter_ptr].to_1d_vec(),
-                gdk_pixbuf::Colorspace::Rgb,
-                false,
-                8,
-                image_width,
-                image_height,
-                image_width * 3,
-            );
-            //            let pixbuf_2 = gdk_pixbuf::Pixbuf::new_from_mut_slice( (*image_dwt_vec)[*counterPtr].to_1d_vec(), gdk_pixbuf::Colorspace::Rgb, false, 8, image_width, image_height, image_width*3);
-
-            let pixbuf_2 = gdk_pixbuf::Pixbuf::new_from_mut_slice(
-                (*image_dwt_vec)[*counter_ptr].to_1d_vec(),
-                gdk_pixbuf::Colorspace::Rgb,
-                false,
-                8,
-                image_width,
-                image_height,
-                image_width * 3,
-            );
-
-            image_1_clone.set_from_pixbuf(&pixbuf_1);
-            image_2_clone.set_from_pixbuf(&pixbuf_2);
+            width as i32,
+            height as i32,
+            width as i32 * 3,
+        ))
+    }
 
-            label_0_clone.set_label(
-                (*image_dct_vec)[*counter_ptr]
-                    .get_coefficient()
-                    .to_string()
-                    .as_str(),
-            );
+    // the widget keeps its previous frame when the image cannot be shown
+    fn show_image(widget: &gtk::Image, image: &Image) {
+        match pixbuf(image) {
+            Ok(pixbuf) => widget.set_from_pixbuf(&pixbuf),
+            Err(err) => error_dialog(&format!("Problem showing the image: {}", err)),
+        }
+    }
 
-            *counter_ptr += 1;
+    // the choices a codec offers, None when it has none
+    fn choice_combo(codec: &dyn Codec) -> Option<gtk::ComboBoxText> {
+        let choice = codec.choice()?;
+        let combo = gtk::ComboBoxText::new();
+        for name in codec.choices() {
+            combo.append(Some(name), name);
+        }
+        combo.set_active_id(Some(choice));
+        Some(combo)
+    }
 
-            gtk::Continue(true)
-        });
+    // the reconstruction is written to the output directory as <input stem>_<codec>_<coefficient>.png
+    fn save_image(image: &Image, name: &str, codec: &str) {
+        let path = format!("{}_{}_{}.png", name, codec, image.get_coefficient());
+        match image.save(std::path::Path::new(&path)) {
+            Ok(()) => println!("saved {}", path),
+            Err(err) => error_dialog(&format!("Problem saving {}: {}", path, err)),
+        }
+    }
 
-        button.connect_clicked(move |_| {
-            let mut pasue_flag = pause_clone.lock().unwrap();
-            *pasue_flag = !*pasue_flag;
-            if *pasue_flag {
-                button_clone.set_label("Resume");
-            } else {
-                button_clone.set_label("Pause");
-            }
-        });
+    fn build_ui(
+        application: &gtk::Application,
+        config: &Config,
+        image_rgb: &Image,
+        name: &str,
+    ) -> Result<(), Error> {
+        use std::sync::{Arc, Mutex};
 
-        button_2.connect_clicked(move |_| {
-            let mut counter_ptr = counter_clone_1.lock().unwrap();
-            *counter_ptr = 0;
+        let image_rgb = Rc::new(image_rgb.clone());
+        let stem = name;
+        // saved images go to the output directory
+        let name = config.output.join(name).to_string_lossy().into_owned();
 
-            *pause_clone_1.lock().unwrap() = false;
+        /*encode with every codec*/
+        let config = Rc::new(config.clone());
+        let mut series = codec::all(&config)?
+            .into_iter()
+            .map(|codec| encode_series(codec, &config, &image_rgb))
+            .collect::<Result<Vec<Series>, Error>>()?;
+        let max_iteration = series[0].budgets.len();
 
-            if *counter_ptr >= max_iteration {
-                return;
+        let mut points = Vec::with_capacity(series.len() * max_iteration);
+        for i in 0..max_iteration {
+            for series in series.iter_mut() {
+                points.push(series.frame(i)?.point.clone());
             }
-
-            let image_dct_series = image_dct_clone.lock().unwrap();
-            let image_dwt_series = image_dwt_clone.lock().unwrap();
-
-            let pixbuf_1 = gdk_pixbuf::Pixbuf::new_from_mut_slice(
-                (*image_dct_series)[*counter_ptr].to_1d_vec(),
-                gdk_pixbuf::Colorspace::Rgb,
-                false,
-                8,
-                image_width,
-                image_height,
-                image_width * 3,
-            );
-            let pixbuf_2 = gdk_pixbuf::Pixbuf::new_from_mut_slice(
-                (*image_dwt_series)[*counter_ptr].to_1d_vec(),
-                gdk_pixbuf::Colorspace::Rgb,
-                false,
-                8,
-                image_width,
-                image_height,
-                image_width * 3,
-            );
-
-            image_1_clone_clone.set_from_pixbuf(&pixbuf_1);
-            image_2_clone_clone.set_from_pixbuf(&pixbuf_2);
-
-            label_0_clone_clone.set_label(
-                (*image_dct_series)[*counter_ptr]
-                    .get_coefficient()
-                    .to_string()
-                    .as_str(),
-            );
-
-            *counter_ptr += 1;
-        });
-
-        grid.attach(&label_0, 0, 0, 2, 1);
-        grid.attach(&label1, 0, 1, 1, 1);
-        grid.attach(&label2, 1, 1, 1, 1);
-        grid.attach(&image_1, 0, 2, 1, 1);
-        grid.attach(&image_2, 1, 2, 1, 1);
-
-        grid.attach(&button, 0, 3, 1, 1);
-        grid.attach(&button_2, 1, 3, 1, 1);
-
-        grid.set_column_spacing(20);
-        grid.set_row_spacing(20);
-
-        window.add(&grid);
-
-        window.show_all();
-    } else {
-        let image_rgb =
-            Image::new_from_rgb(width, height, coefficient, blocksize, &content).unwrap();
-
-        let mut image_dct = image_rgb.clone();
-        let mut image_dwt = image_rgb.clone();
-
-        /*encode using dct or dwt*/
-        image_dct.dct_encode();
-        image_dct.dct_decode();
-
-        image_dwt.dwt_encode();
-        image_dwt.dwt_decode();
+        }
+        write_rd(&config, stem, &points);
 
         let window = gtk::ApplicationWindow::new(application);
 
@@ -270,43 +299,163 @@ fn build_ui(application: &gtk::Application) {
         window.set_default_size(1064, 630);
 
         let grid = gtk::Grid::new();
-        let label_0 = gtk::Label::new(config.coefficient.to_string().as_str());
-        let label1 = gtk::Label::new("dct");
-        let label2 = gtk::Label::new("dwt");
-
-        let image_width: i32 = width as i32;
-        let image_height: i32 = height as i32;
-
-        let image_data_1 = image_dct.to_1d_vec();
-        let pixbuf_1 = gdk_pixbuf::Pixbuf::new_from_mut_slice(
-            image_data_1,
-            gdk_pixbuf::Colorspace::Rgb,
-            false,
-            8,
-            image_width,
-            image_height,
-            image_width * 3,
-        );
-
-        let image_data_2 = image_dwt.to_1d_vec();
-        let pixbuf_2 = gdk_pixbuf::Pixbuf::new_from_mut_slice(
-            image_data_2,
-            gdk_pixbuf::Colorspace::Rgb,
-            false,
-            8,
-            image_width,
-            image_height,
-            image_width * 3,
-        );
+        let columns = Columns {
+            budget: gtk::Label::new(budget_label(&series, 0).as_str()),
+            images: series
+                .iter_mut()
+                .map(|series| {
+                    Ok(gtk::Image::new_from_pixbuf(&pixbuf(
+                        &series.frame(0)?.image,
+                    )?))
+                })
+                .collect::<Result<Vec<gtk::Image>, Error>>()?,
+            results: series
+                .iter_mut()
+                .map(|series| Ok(gtk::Label::new(series.frame(0)?.result.as_str())))
+                .collect::<Result<Vec<gtk::Label>, Error>>()?,
+        };
+        let width = series.len() as i32;
+
+        grid.attach(&columns.budget, 0, 0, width, 1);
+        for (c, series) in series.iter().enumerate() {
+            let column = c as i32;
+            let label = gtk::Label::new(series.codec.describe(&image_rgb).as_str());
+            grid.attach(&label, column, 1, 1, 1);
+            grid.attach(&columns.images[c], column, 2, 1, 1);
+            grid.attach(&columns.results[c], column, 3, 1, 1);
+        }
 
-        let image_1 = gtk::Image::new_from_pixbuf(&pixbuf_1);
-        let image_2 = gtk::Image::new_from_pixbuf(&pixbuf_2);
+        let choices: Vec<Option<gtk::ComboBoxText>> = series
+            .iter()
+            .map(|series| choice_combo(series.codec.as_ref()))
+            .collect();
+        let save_labels: Vec<String> = series
+            .iter()
+            .map(|series| format!("Save {}", series.codec.name().to_uppercase()))
+            .collect();
+        let series = Rc::new(RefCell::new(series));
+        let counter = Arc::new(Mutex::new(1));
+        let mut row = 4;
+
+        if config.coefficient == -1 {
+            let button = gtk::Button::new_with_label("Pasue");
+            let button_clone = button.clone();
+            let button_2 = gtk::Button::new_with_label("Restart");
+
+            let pause = Arc::new(Mutex::new(false));
+            let pause_clone = Arc::clone(&pause);
+            let pause_clone_1 = Arc::clone(&pause);
+
+            let interval = 800;
+            let counter_clone = Arc::clone(&counter);
+            let counter_clone_1 = Arc::clone(&counter);
+            let series_clone = Rc::clone(&series);
+            let series_clone_1 = Rc::clone(&series);
+            let columns_clone = columns.clone();
+            let columns_clone_1 = columns.clone();
+            gtk::timeout_add(interval, move || {
+                if *pause.lock().unwrap() {
+                    return gtk::Continue(true);
+                }
+
+                // an error dialog of the previous frame may still be open
+                let mut series = match series_clone.try_borrow_mut() {
+                    Ok(series) => series,
+                    Err(_) => return gtk::Continue(true),
+                };
+                let mut counter_ptr = counter_clone.lock().unwrap();
+
+                if *counter_ptr >= max_iteration {
+                    return gtk::Continue(true);
+                }
+
+                show_frame(&columns_clone, &mut series, *counter_ptr);
+
+                *counter_ptr += 1;
+
+                gtk::Continue(true)
+            });
+
+            button.connect_clicked(move |_| {
+                let mut pasue_flag = pause_clone.lock().unwrap();
+                *pasue_flag = !*pasue_flag;
+                if *pasue_flag {
+                    button_clone.set_label("Resume");
+                } else {
+                    button_clone.set_label("Pause");
+                }
+            });
+
+            button_2.connect_clicked(move |_| {
+                let mut counter_ptr = counter_clone_1.lock().unwrap();
+                *counter_ptr = 0;
+
+                *pause_clone_1.lock().unwrap() = false;
+
+                show_frame(
+                    &columns_clone_1,
+                    &mut series_clone_1.borrow_mut(),
+                    *counter_ptr,
+                );
+
+                *counter_ptr += 1;
+            });
+
+            grid.attach(&button, 0, row, 1, 1);
+            grid.attach(&button_2, 1, row, 1, 1);
+            row += 1;
+        }
 
-        grid.attach(&label_0, 0, 0, 2, 1);
-        grid.attach(&label1, 0, 1, 1, 1);
-        grid.attach(&label2, 1, 1, 1, 1);
-        grid.attach(&image_1, 0, 2, 1, 1);
-        grid.attach(&image_2, 1, 2, 1, 1);
+        // the frame on show is saved, the wavelet is part of the name of the codecs using it
+        for (c, label) in save_labels.iter().enumerate() {
+            let button = gtk::Button::new_with_label(label);
+            let counter_clone = Arc::clone(&counter);
+            let series_clone = Rc::clone(&series);
+            let name_clone = name.clone();
+            button.connect_clicked(move |_| {
+                let index = (*counter_clone.lock().unwrap()).clamp(1, max_iteration) - 1;
+                let series = &mut series_clone.borrow_mut()[c];
+                let tag = series.tag.clone();
+                match series.frame(index) {
+                    Ok(frame) => save_image(&frame.image, &name_clone, &tag),
+                    Err(err) => error_dialog(&format!("Problem encoding: {}", err)),
+                }
+            });
+            grid.attach(&button, c as i32, row, 1, 1);
+        }
+        row += 1;
+
+        // a codec with choices, like the wavelet of the DWT, gets a combo box under its column,
+        // another choice only encodes the frame on show at once and the others when they are shown
+        for (c, combo) in choices.into_iter().enumerate() {
+            let combo = match combo {
+                Some(combo) => combo,
+                None => continue,
+            };
+            let counter = Arc::clone(&counter);
+            let series = Rc::clone(&series);
+            let columns = columns.clone();
+            let image_rgb = Rc::clone(&image_rgb);
+            let config = Rc::clone(&config);
+            combo.connect_changed(move |combo| {
+                let mut series = series.borrow_mut();
+                let chosen = match combo
+                    .get_active_id()
+                    .and_then(|id| series[c].codec.with_choice(&id))
+                {
+                    Some(chosen) => chosen,
+                    None => return,
+                };
+                series[c] = match Series::new(chosen, &config, &image_rgb) {
+                    Ok(chosen) => chosen,
+                    Err(err) => return error_dialog(&format!("Problem encoding: {}", err)),
+                };
+
+                let index = (*counter.lock().unwrap()).clamp(1, max_iteration) - 1;
+                show_frame(&columns, &mut series, index);
+            });
+            grid.attach(&combo, c as i32, row, 1, 1);
+        }
 
         grid.set_column_spacing(20);
         grid.set_row_spacing(20);
@@ -314,17 +463,65 @@ fn build_ui(application: &gtk::Application) {
         window.add(&grid);
 
         window.show_all();
+        Ok(())
+    }
+
+    // the window on the reconstructions of every codec, an error reading the image is shown in a
+    // dialog
+    pub fn run(config: Config, image_rgb: Result<Image, Error>, name: String) {
+        let application =
+            gtk::Application::new("com.github.gtk-rs.examples.basic", Default::default())
+                .expect("Initialization failed...");
+
+        // without a window the application quits once the dialog is closed
+        application.connect_activate(move |app| {
+            let result = match &image_rgb {
+                Ok(image_rgb) => build_ui(app, &config, image_rgb, &name),
+                Err(err) => return error_dialog(&format!("Problem reading image: {}", err)),
+            };
+            if let Err(err) = result {
+                error_dialog(&format!("Problem converting image: {}", err));
+            }
+        });
+
+        let empty: Vec<String> = Vec::new();
+        application.run(&empty);
     }
 }
 
 fn main() {
-    let application = gtk::Application::new("com.github.gtk-rs.examples.basic", Default::default())
-        .expect("Initialization failed...");
-
-    application.connect_activate(|app| {
-        build_ui(app);
+    let config = Config::new(std::env::args()).unwrap_or_else(|err| {
+        if err == ArgsError::Help {
+            print!("{}", err);
+            std::process::exit(0);
+        }
+        eprintln!("Problem parsing arguments: {}", err);
+        std::process::exit(1);
     });
 
-    let empty: Vec<String> = Vec::new();
-    application.run(&empty);
+    if !config.headless && !cfg!(feature = "gui") {
+        eprintln!("Problem opening the window: built without the gui feature, use --headless");
+        std::process::exit(1);
+    }
+
+    let image_rgb = load_image(&config);
+    // a path without a file name, e.g. /, leaves the reading error to be reported
+    let name = config.path.file_stem().map_or_else(
+        || "image".to_string(),
+        |stem| stem.to_string_lossy().into_owned(),
+    );
+
+    #[cfg(feature = "gui")]
+    if !config.headless {
+        return window::run(config, image_rgb, name);
+    }
+
+    let image_rgb = image_rgb.unwrap_or_else(|err| {
+        eprintln!("Problem reading image: {}", err);
+        std::process::exit(1);
+    });
+    if let Err(err) = headless::run(&config, &image_rgb, &name, &mut std::io::stdout()) {
+        eprintln!("Problem writing results: {}", err);
+        std::process::exit(1);
+    }
 }
@@ -0,0 +1,372 @@
+/*Quality Metrics*/
+
+// a metric of the R, G and B channels and of all of them together
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Measure {
+    pub channels: [f64; 3],
+    pub overall: f64,
+}
+
+// mean squared error between two equally long sample sequences
+pub fn mse(a: &[u8], b: &[u8]) -> f64 {
+    assert_eq!(a.len(), b.len(), "mse, samples differ in length");
+    if a.is_empty() {
+        return 0.0;
+    }
+    let sum: f64 = a
+        .iter()
+        .zip(b)
+        .map(|(&a, &b)| {
+            let d = f64::from(a) - f64::from(b);
+            d * d
+        })
+        .sum();
+    sum / a.len() as f64
+}
+
+// peak signal to noise ratio of 8 bit samples in dB, infinite when there is no error
+pub fn psnr(mse: f64) -> f64 {
+    10.0 * (255.0 * 255.0 / mse).log10()
+}
+
+/*Structural Similarity*/
+
+// constants of Wang et al., "Image Quality Assessment: From Error Visibility to Structural
+// Similarity", 2004, for 8 bit samples
+const C1: f64 = (0.01 * 255.0) * (0.01 * 255.0);
+const C2: f64 = (0.03 * 255.0) * (0.03 * 255.0);
+const WINDOW: usize = 11;
+const SIGMA: f64 = 1.5;
+
+// weights of the five scales of multi-scale SSIM, Wang et al. 2003
+const SCALE_WEIGHTS: [f64; 5] = [0.0448, 0.2856, 0.3001, 0.2363, 0.1333];
+
+// mean SSIM of one channel with an 11 x 11 Gaussian window (σ = 1.5) over every position where
+// the window fits, the window shrinks for images smaller than it
+pub fn ssim(a: &[u8], b: &[u8], width: usize, height: usize) -> f64 {
+    let (a, b) = (to_f64(a), to_f64(b));
+    ssim_terms(&a, &b, width, height).0
+}
+
+// multi-scale SSIM of one channel, the contrast and structure of five scales halved in between
+// and the luminance of the coarsest one, fewer scales are used when the image is too small
+pub fn ms_ssim(a: &[u8], b: &[u8], width: usize, height: usize) -> f64 {
+    let (mut a, mut b) = (to_f64(a), to_f64(b));
+    let (mut width, mut height) = (width, height);
+
+    let mut scales = 1;
+    while scales < SCALE_WEIGHTS.len() && std::cmp::min(width, height) >> scales >= WINDOW {
+        scales += 1;
+    }
+    let weights = &SCALE_WEIGHTS[..scales];
+    let total: f64 = weights.iter().sum();
+
+    let mut ms_ssim = 1.0;
+    for (scale, weight) in weights.iter().enumerate() {
+        let (ssim, cs) = ssim_terms(&a, &b, width, height);
+        // negative similarity is clipped so that the weighted product stays defined
+        let term = if scale + 1 == scales { ssim } else { cs };
+        ms_ssim *= term.max(0.0).powf(weight / total);
+
+        a = halve(&a, width, height);
+        b = halve(&b, width, height);
+        width /= 2;
+        height /= 2;
+    }
+    ms_ssim
+}
+
+fn to_f64(samples: &[u8]) -> Vec<f64> {
+    samples.iter().map(|&s| f64::from(s)).collect()
+}
+
+// mean SSIM and mean contrast structure term
+fn ssim_terms(a: &[f64], b: &[f64], width: usize, height: usize) -> (f64, f64) {
+    assert!(
+        a.len() == width * height && b.len() == width * height,
+        "ssim, samples differ from the size"
+    );
+    if a.is_empty() {
+        return (1.0, 1.0);
+    }
+
+    let window = gaussian(std::cmp::min(WINDOW, std::cmp::min(width, height)));
+    let product =
+        |x: &[f64], y: &[f64]| -> Vec<f64> { x.iter().zip(y).map(|(x, y)| x * y).collect() };
+    let (mu_a, w, h) = filter(a, width, height, &window);
+    let (mu_b, _, _) = filter(b, width, height, &window);
+    let (aa, _, _) = filter(&product(a, a), width, height, &window);
+    let (bb, _, _) = filter(&product(b, b), width, height, &window);
+    let (ab, _, _) = filter(&product(a, b), width, height, &window);
+
+    let (mut ssim, mut cs) = (0.0, 0.0);
+    for i in 0..w * h {
+        let (mu_a, mu_b) = (mu_a[i], mu_b[i]);
+        let sigma_a = aa[i] - mu_a * mu_a;
+        let sigma_b = bb[i] - mu_b * mu_b;
+        let sigma_ab = ab[i] - mu_a * mu_b;
+
+        let contrast_structure = (2.0 * sigma_ab + C2) / (sigma_a + sigma_b + C2);
+        let luminance = (2.0 * mu_a * mu_b + C1) / (mu_a * mu_a + mu_b * mu_b + C1);
+        ssim += luminance * contrast_structure;
+        cs += contrast_structure;
+    }
+    let count = (w * h) as f64;
+    (ssim / count, cs / count)
+}
+
+// normalized 1D Gaussian of the given length
+fn gaussian(length: usize) -> Vec<f64> {
+    let center = (length - 1) as f64 / 2.0;
+    let window: Vec<f64> = (0..length)
+        .map(|i| (-((i as f64 - center).powi(2)) / (2.0 * SIGMA * SIGMA)).exp())
+        .collect();
+    let sum: f64 = window.iter().sum();
+    window.into_iter().map(|w| w / sum).collect()
+}
+
+// separable filter keeping only the positions where the whole window fits
+fn filter(x: &[f64], width: usize, height: usize, window: &[f64]) -> (Vec<f64>, usize, usize) {
+    let (w, h) = (width + 1 - window.len(), height + 1 - window.len());
+
+    let mut rows = vec![0.0; w * height];
+    for y in 0..height {
+        let row = &x[y * width..(y + 1) * width];
+        for i in 0..w {
+            rows[y * w + i] = row[i..i + window.len()]
+                .iter()
+                .zip(window)
+                .map(|(x, k)| x * k)
+                .sum();
+        }
+    }
+
+    let mut filtered = vec![0.0; w * h];
+    for j in 0..h {
+        for i in 0..w {
+            filtered[j * w + i] = window
+                .iter()
+                .enumerate()
+                .map(|(k, weight)| rows[(j + k) * w + i] * weight)
+                .sum();
+        }
+    }
+    (filtered, w, h)
+}
+
+// 2 x 2 averages, an odd last row or column is dropped
+fn halve(x: &[f64], width: usize, height: usize) -> Vec<f64> {
+    let (w, h) = (width / 2, height / 2);
+    let mut halved = vec![0.0; w * h];
+    for y in 0..h {
+        for i in 0..w {
+            let top = 2 * y * width + 2 * i;
+            let bottom = top + width;
+            halved[y * w + i] = (x[top] + x[top + 1] + x[bottom] + x[bottom + 1]) / 4.0;
+        }
+    }
+    halved
+}
+
+/*Artifacts*/
+
+// Sobel gradient magnitude from which a sample of the original counts as an edge
+const EDGE: f64 = 128.0;
+// distance from an edge, in samples, within which ringing is looked for
+const RINGING_RADIUS: usize = 3;
+
+// mean absolute difference between neighbours across the block boundaries minus the one
+// between neighbours inside the blocks, in sample levels, about 0 when the block grid does not
+// stand out and growing with the steps at the block edges
+pub fn blockiness(a: &[u8], width: usize, height: usize, block_x: usize, block_y: usize) -> f64 {
+    assert_eq!(
+        a.len(),
+        width * height,
+        "blockiness, samples differ from the size"
+    );
+    let (mut across, mut inside) = ((0.0, 0), (0.0, 0));
+    let mut add = |boundary: bool, p: u8, q: u8| {
+        let sum = if boundary { &mut across } else { &mut inside };
+        sum.0 += (f64::from(p) - f64::from(q)).abs();
+        sum.1 += 1;
+    };
+
+    for y in 0..height {
+        for x in 1..width {
+            add(
+                x.is_multiple_of(block_x),
+                a[y * width + x],
+                a[y * width + x - 1],
+            );
+        }
+    }
+    for y in 1..height {
+        for x in 0..width {
+            add(
+                y.is_multiple_of(block_y),
+                a[y * width + x],
+                a[(y - 1) * width + x],
+            );
+        }
+    }
+
+    let mean = |(sum, count): (f64, usize)| if count == 0 { 0.0 } else { sum / count as f64 };
+    mean(across) - mean(inside)
+}
+
+// mean absolute error next to the edges of the original, within RINGING_RADIUS samples of them
+// but not on them, where ringing and blur around edges show, 0 when the original has no edges
+pub fn ringing(original: &[u8], b: &[u8], width: usize, height: usize) -> f64 {
+    assert!(
+        original.len() == width * height && b.len() == width * height,
+        "ringing, samples differ from the size"
+    );
+    let edges = edges(original, width, height);
+
+    let mut near = vec![false; width * height];
+    for y in 0..height {
+        for x in 0..width {
+            if !edges[y * width + x] {
+                continue;
+            }
+            let (x_start, y_start) = (
+                x.saturating_sub(RINGING_RADIUS),
+                y.saturating_sub(RINGING_RADIUS),
+            );
+            let x_end = std::cmp::min(x + RINGING_RADIUS + 1, width);
+            let y_end = std::cmp::min(y + RINGING_RADIUS + 1, height);
+            for j in y_start..y_end {
+                for i in x_start..x_end {
+                    near[j * width + i] = true;
+                }
+            }
+        }
+    }
+
+    let (mut sum, mut count) = (0.0, 0);
+    for i in 0..width * height {
+        if near[i] && !edges[i] {
+            sum += (f64::from(original[i]) - f64::from(b[i])).abs();
+            count += 1;
+        }
+    }
+    if count == 0 {
+        0.0
+    } else {
+        sum / count as f64
+    }
+}
+
+// samples whose Sobel gradient magnitude reaches EDGE, the outermost rows and columns never do
+fn edges(a: &[u8], width: usize, height: usize) -> Vec<bool> {
+    let mut edges = vec![false; width * height];
+    let at = |x: usize, y: usize| f64::from(a[y * width + x]);
+    for y in 1..height.saturating_sub(1) {
+        for x in 1..width.saturating_sub(1) {
+            let gx = at(x + 1, y - 1) + 2.0 * at(x + 1, y) + at(x + 1, y + 1)
+                - at(x - 1, y - 1)
+                - 2.0 * at(x - 1, y)
+                - at(x - 1, y + 1);
+            let gy = at(x - 1, y + 1) + 2.0 * at(x, y + 1) + at(x + 1, y + 1)
+                - at(x - 1, y - 1)
+                - 2.0 * at(x, y - 1)
+                - at(x + 1, y - 1);
+            edges[y * width + x] = gx.hypot(gy) >= EDGE;
+        }
+    }
+    edges
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn mse_and_psnr() {
+        assert_eq!(mse(&[1, 2, 3, 4], &[1, 2, 3, 4]), 0.0);
+        assert_eq!(mse(&[0, 10], &[2, 6]), 10.0);
+        assert_eq!(psnr(0.0), f64::INFINITY);
+        assert!((psnr(1.0) - 48.130_803_608).abs() < 1e-6);
+        assert!((psnr(255.0 * 255.0)).abs() < 1e-12);
+    }
+
+    fn test_channel(width: usize, height: usize) -> Vec<u8> {
+        (0..width * height)
+            .map(|i| ((i % width) * 3 + (i / width) * 5 + (i * 7919) % 23) as u8)
+            .collect()
+    }
+
+    #[test]
+    fn ssim_of_identical_and_distorted() {
+        let (width, height) = (40, 32);
+        let a = test_channel(width, height);
+        assert!((ssim(&a, &a, width, height) - 1.0).abs() < 1e-12);
+        assert!((ms_ssim(&a, &a, width, height) - 1.0).abs() < 1e-12);
+
+        let slightly: Vec<u8> = a.iter().map(|&s| s.saturating_add(2)).collect();
+        let noisy: Vec<u8> = a
+            .iter()
+            .enumerate()
+            .map(|(i, &s)| if i % 2 == 0 { s.saturating_add(40) } else { s })
+            .collect();
+        let slight = ssim(&a, &slightly, width, height);
+        let noise = ssim(&a, &noisy, width, height);
+        assert!(slight < 1.0 && slight > 0.95);
+        assert!(noise < slight);
+        assert!(ms_ssim(&a, &noisy, width, height) < ms_ssim(&a, &slightly, width, height));
+    }
+
+    #[test]
+    fn ssim_of_small_images() {
+        let a = test_channel(3, 2);
+        assert!((ssim(&a, &a, 3, 2) - 1.0).abs() < 1e-12);
+        assert!((ms_ssim(&a, &a, 3, 2) - 1.0).abs() < 1e-12);
+        assert_eq!(ssim(&[], &[], 0, 0), 1.0);
+    }
+
+    #[test]
+    fn gaussian_window() {
+        let window = gaussian(11);
+        assert!((window.iter().sum::<f64>() - 1.0).abs() < 1e-12);
+        assert!((window[0] - window[10]).abs() < 1e-15);
+        assert!(window[5] > window[4]);
+    }
+
+    #[test]
+    fn blockiness_of_blocks_and_ramps() {
+        let (width, height) = (16, 16);
+        // a ramp steps by the same amount everywhere, constant 8 x 8 blocks only at their edges
+        let ramp: Vec<u8> = (0..width * height).map(|i| (i % width * 4) as u8).collect();
+        let blocks: Vec<u8> = (0..width * height)
+            .map(|i| ((i % width / 8) * 40 + (i / width / 8) * 80) as u8)
+            .collect();
+        assert!(blockiness(&ramp, width, height, 8, 8).abs() < 1e-12);
+        assert!(blockiness(&blocks, width, height, 8, 8) > 10.0);
+        assert!(
+            blockiness(&blocks, width, height, 8, 8) > blockiness(&blocks, width, height, 5, 5)
+        );
+        assert_eq!(blockiness(&[7], 1, 1, 8, 8), 0.0);
+    }
+
+    #[test]
+    fn ringing_next_to_edges() {
+        let (width, height) = (16, 8);
+        let step: Vec<u8> = (0..width * height)
+            .map(|i| if i % width < 8 { 20 } else { 220 })
+            .collect();
+        assert_eq!(ringing(&step, &step, width, height), 0.0);
+
+        // an overshoot next to the step counts, the same error far from it does not
+        let mut overshoot = step.clone();
+        let mut far = step.clone();
+        for y in 0..height {
+            overshoot[y * width + 10] += 20;
+            far[y * width + 15] += 20;
+        }
+        assert!(ringing(&step, &overshoot, width, height) > 1.0);
+        assert_eq!(ringing(&step, &far, width, height), 0.0);
+
+        let flat = vec![100; width * height];
+        assert_eq!(ringing(&flat, &step, width, height), 0.0);
+    }
+}
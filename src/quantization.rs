@@ -0,0 +1,224 @@
+use std::io::{Error, ErrorKind};
+
+/*JPEG Quantization Tables*/
+
+// ITU-T T.81 Annex K, Table K.1
+static LUMINANCE: [u16; 64] = [
+    16, 11, 10, 16, 24, 40, 51, 61, //
+    12, 12, 14, 19, 26, 58, 60, 55, //
+    14, 13, 16, 24, 40, 57, 69, 56, //
+    14, 17, 22, 29, 51, 87, 80, 62, //
+    18, 22, 37, 56, 68, 109, 103, 77, //
+    24, 35, 55, 64, 81, 104, 113, 92, //
+    49, 64, 78, 87, 103, 121, 120, 101, //
+    72, 92, 95, 98, 112, 100, 103, 99, //
+];
+
+// ITU-T T.81 Annex K, Table K.2
+static CHROMINANCE: [u16; 64] = [
+    17, 18, 24, 47, 99, 99, 99, 99, //
+    18, 21, 26, 66, 99, 99, 99, 99, //
+    24, 26, 56, 99, 99, 99, 99, 99, //
+    47, 66, 99, 99, 99, 99, 99, 99, //
+    99, 99, 99, 99, 99, 99, 99, 99, //
+    99, 99, 99, 99, 99, 99, 99, 99, //
+    99, 99, 99, 99, 99, 99, 99, 99, //
+    99, 99, 99, 99, 99, 99, 99, 99, //
+];
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct QuantizationTable {
+    width: usize,
+    height: usize,
+    values: Vec<u16>, // row major
+}
+
+impl QuantizationTable {
+    pub fn new(width: usize, height: usize, values: Vec<u16>) -> Result<Self, Error> {
+        if width == 0 || height == 0 || values.len() != width * height {
+            return Err(Error::new(
+                ErrorKind::InvalidData,
+                "quantization table size does not match its values",
+            ));
+        }
+        if values.contains(&0) {
+            return Err(Error::new(
+                ErrorKind::InvalidData,
+                "quantization table values must be at least 1",
+            ));
+        }
+        Ok(Self {
+            width,
+            height,
+            values,
+        })
+    }
+
+    pub fn luminance() -> Self {
+        Self::new(8, 8, LUMINANCE.to_vec()).unwrap()
+    }
+
+    pub fn chrominance() -> Self {
+        Self::new(8, 8, CHROMINANCE.to_vec()).unwrap()
+    }
+
+    // IJG quality scaling, 50 keeps the table as is, 100 is all ones
+    pub fn scaled(&self, quality: u8) -> Self {
+        let quality = u32::from(quality.clamp(1, 100));
+        let scale = if quality < 50 {
+            5000 / quality
+        } else {
+            200 - 2 * quality
+        };
+        let values = self
+            .values
+            .iter()
+            .map(|&q| ((u32::from(q) * scale + 50) / 100).clamp(1, 255) as u16)
+            .collect();
+        Self {
+            width: self.width,
+            height: self.height,
+            values,
+        }
+    }
+
+    // step sizes for a block_width x block_height block, table[v][u], frequencies are
+    // mapped onto the table by nearest neighbour when the sizes differ
+    pub fn steps(&self, block_width: usize, block_height: usize) -> Vec<Vec<f64>> {
+        (0..block_height)
+            .map(|v| {
+                let row = v * self.height / block_height;
+                (0..block_width)
+                    .map(|u| {
+                        let column = u * self.width / block_width;
+                        f64::from(self.values[row * self.width + column])
+                    })
+                    .collect()
+            })
+            .collect()
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Quantization {
+    pub luminance: QuantizationTable,
+    pub chrominance: QuantizationTable,
+    pub quality: u8,
+}
+
+impl Quantization {
+    pub fn new(luminance: QuantizationTable, chrominance: QuantizationTable, quality: u8) -> Self {
+        Self {
+            luminance: luminance.scaled(quality),
+            chrominance: chrominance.scaled(quality),
+            quality,
+        }
+    }
+
+    pub fn jpeg(quality: u8) -> Self {
+        Self::new(
+            QuantizationTable::luminance(),
+            QuantizationTable::chrominance(),
+            quality,
+        )
+    }
+
+    // one row of the table per line, a blank line separates the luminance table from an
+    // optional chrominance table, the luminance table is reused when there is none
+    pub fn from_file(path: &std::path::Path, quality: u8) -> Result<Self, Error> {
+        let content = std::fs::read_to_string(path)?;
+        Self::parse(&content, quality)
+    }
+
+    pub fn parse(content: &str, quality: u8) -> Result<Self, Error> {
+        let mut tables: Vec<Vec<Vec<u16>>> = vec![vec![]];
+        for line in content.lines() {
+            let line = line.trim();
+            if line.is_empty() {
+                if !tables.last().unwrap().is_empty() {
+                    tables.push(vec![]);
+                }
+                continue;
+            }
+            let row = line
+                .split(|c: char| c.is_whitespace() || c == ',')
+                .filter(|value| !value.is_empty())
+                .map(|value| value.parse::<u16>())
+                .collect::<Result<Vec<u16>, _>>()
+                .map_err(|_| Error::new(ErrorKind::InvalidData, "not a valid table value"))?;
+            tables.last_mut().unwrap().push(row);
+        }
+        if tables.last().unwrap().is_empty() {
+            tables.pop();
+        }
+
+        let tables = tables
+            .into_iter()
+            .map(|rows| {
+                let width = rows[0].len();
+                if rows.iter().any(|row| row.len() != width) {
+                    return Err(Error::new(
+                        ErrorKind::InvalidData,
+                        "quantization table rows differ in length",
+                    ));
+                }
+                let height = rows.len();
+                QuantizationTable::new(width, height, rows.concat())
+            })
+            .collect::<Result<Vec<QuantizationTable>, Error>>()?;
+
+        match tables.len() {
+            1 => Ok(Self::new(tables[0].clone(), tables[0].clone(), quality)),
+            2 => Ok(Self::new(tables[0].clone(), tables[1].clone(), quality)),
+            _ => Err(Error::new(
+                ErrorKind::InvalidData,
+                "expected one or two quantization tables",
+            )),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn quality_scaling() {
+        let table = QuantizationTable::luminance();
+        assert_eq!(table.scaled(50), table);
+        assert!(table.scaled(100).values.iter().all(|&q| q == 1));
+        assert!(table.scaled(1).values.iter().all(|&q| q == 255));
+        assert_eq!(table.scaled(75).values[0], 8);
+        assert_eq!(table.scaled(25).values[0], 32);
+    }
+
+    #[test]
+    fn steps_for_other_block_sizes() {
+        let table = QuantizationTable::luminance();
+
+        let steps = table.steps(8, 8);
+        assert_eq!(steps[0][1], 11.0);
+        assert_eq!(steps[7][7], 99.0);
+
+        let steps = table.steps(16, 4);
+        assert_eq!(steps.len(), 4);
+        assert_eq!(steps[0].len(), 16);
+        assert_eq!(steps[0][2], 11.0);
+        assert_eq!(steps[1][0], 14.0);
+        assert_eq!(steps[3][15], 101.0);
+    }
+
+    #[test]
+    fn parse_custom_tables() {
+        let quantization = Quantization::parse("1 2\n3 4\n\n5, 6\n7, 8\n", 50).unwrap();
+        assert_eq!(quantization.luminance.values, vec![1, 2, 3, 4]);
+        assert_eq!(quantization.chrominance.values, vec![5, 6, 7, 8]);
+
+        let quantization = Quantization::parse("1 2 3\n", 50).unwrap();
+        assert_eq!(quantization.luminance, quantization.chrominance);
+
+        assert!(Quantization::parse("1 2\n3\n", 50).is_err());
+        assert!(Quantization::parse("1 0\n", 50).is_err());
+        assert!(Quantization::parse("", 50).is_err());
+    }
+}
@@ -0,0 +1,424 @@
+use core::slice::Iter;
+use std::collections::BTreeMap;
+
+use crate::codec::{Codec, Coefficients};
+use crate::config::Config;
+use crate::error::Error;
+use crate::image::Image;
+
+/*Rate Distortion*/
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Format {
+    Csv,
+    Json,
+}
+
+impl Format {
+    pub fn name(&self) -> &'static str {
+        match *self {
+            Format::Csv => "csv",
+            Format::Json => "json",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Format::iterator().find(|f| f.name() == name).cloned()
+    }
+
+    pub fn iterator() -> Iter<'static, Format> {
+        static FORMATS: [Format; 2] = [Format::Csv, Format::Json];
+        FORMATS.iter()
+    }
+}
+
+// one reconstruction of the sweep, bpp is bits per pixel of the image
+#[derive(Clone, Debug, PartialEq)]
+pub struct Point {
+    pub codec: String,
+    pub coefficient: usize,
+    pub bits: f64,
+    pub bpp: f64,
+    pub psnr: f64,
+    pub ssim: f64,
+}
+
+impl Point {
+    // the point of coefficients from the PSNR and SSIM their reconstruction already got
+    pub fn new(codec: &str, coefficients: &Coefficients, psnr: f64, ssim: f64) -> Self {
+        let (width, height) = coefficients.get_size();
+        let bits = coefficients.estimated_bits();
+        Point {
+            codec: codec.to_string(),
+            coefficient: coefficients.get_coefficient(),
+            bits,
+            bpp: bits / (width * height) as f64,
+            psnr,
+            ssim,
+        }
+    }
+}
+
+// the largest budget per channel whose estimated bits stay within bpp bits per pixel when
+// image is encoded by codec, at least one coefficient, found by bisection as estimated_bits
+// does not go down when more coefficients are kept
+pub fn budget_for_bpp(image: &Image, bpp: f64, codec: &dyn Codec) -> Result<usize, Error> {
+    let (width, height) = image.get_size();
+    let target = bpp * (width * height) as f64;
+    let bits = |coefficient: usize| {
+        let mut image = image.clone();
+        image.set_coefficient(coefficient);
+        Ok::<_, Error>(codec.encode(&image)?.estimated_bits())
+    };
+
+    let (mut low, mut high) = (1, width * height);
+    if bits(high)? <= target {
+        return Ok(high);
+    }
+    while high - low > 1 {
+        let middle = low + (high - low) / 2;
+        if bits(middle)? <= target {
+            low = middle;
+        } else {
+            high = middle;
+        }
+    }
+    Ok(low)
+}
+
+// zeroth order entropy estimate of coding the coefficients of one plane rounded to integers,
+// the ones not kept are coded as 0 in the same alphabet, no side information
+pub fn estimated_bits(coefficients: &[f64]) -> f64 {
+    // ordered so that the same coefficients always sum to the same bits
+    let mut counts: BTreeMap<i64, usize> = BTreeMap::new();
+    for f in coefficients {
+        *counts.entry(f.round() as i64).or_insert(0) += 1;
+    }
+
+    // bits of count symbols that each occur with probability count / total
+    let total = coefficients.len() as f64;
+    counts
+        .values()
+        .map(|&count| -(count as f64) * (count as f64 / total).log2())
+        .sum()
+}
+
+// writes <name>_rd.csv or <name>_rd.json and the plot <name>_rd.svg to the output directory
+// when the configuration asks for them
+pub fn write(config: &Config, name: &str, points: &[Point]) -> Result<(), std::io::Error> {
+    if config.rd.is_none() && !config.plot {
+        return Ok(());
+    }
+    std::fs::create_dir_all(&config.output)?;
+
+    if let Some(format) = config.rd {
+        let table = match format {
+            Format::Csv => to_csv(points),
+            Format::Json => to_json(points),
+        };
+        let path = config.output.join(format!("{}_rd.{}", name, format.name()));
+        std::fs::write(path, table)?;
+    }
+    if config.plot {
+        let path = config.output.join(format!("{}_rd.svg", name));
+        std::fs::write(path, to_svg(points, name))?;
+    }
+    Ok(())
+}
+
+pub fn to_csv(points: &[Point]) -> String {
+    let mut table = "codec,coefficient,bits,bpp,psnr,ssim\n".to_string();
+    for point in points {
+        table += &format!(
+            "{},{},{:.0},{:.4},{:.4},{:.4}\n",
+            point.codec, point.coefficient, point.bits, point.bpp, point.psnr, point.ssim
+        );
+    }
+    table
+}
+
+// an array of objects, the infinite PSNR of a lossless reconstruction is null
+pub fn to_json(points: &[Point]) -> String {
+    let number = |value: f64| {
+        if value.is_finite() {
+            format!("{:.4}", value)
+        } else {
+            "null".to_string()
+        }
+    };
+    let rows: Vec<String> = points
+        .iter()
+        .map(|point| {
+            format!(
+                "  {{\"codec\": \"{}\", \"coefficient\": {}, \"bits\": {:.0}, \"bpp\": {}, \"psnr\": {}, \"ssim\": {}}}",
+                point.codec,
+                point.coefficient,
+                point.bits,
+                number(point.bpp),
+                number(point.psnr),
+                number(point.ssim)
+            )
+        })
+        .collect();
+    format!("[\n{}\n]\n", rows.join(",\n"))
+}
+
+/*SVG Plot*/
+
+const PLOT_WIDTH: f64 = 640.0;
+const PLOT_HEIGHT: f64 = 400.0;
+const MARGIN: f64 = 50.0;
+const COLORS: [&str; 4] = ["#1f77b4", "#d62728", "#2ca02c", "#9467bd"];
+
+// PSNR over bits per pixel with one line per codec, points with an infinite PSNR are left out
+pub fn to_svg(points: &[Point], title: &str) -> String {
+    let finite: Vec<&Point> = points.iter().filter(|p| p.psnr.is_finite()).collect();
+    let range = |value: fn(&Point) -> f64| {
+        let min = finite
+            .iter()
+            .map(|p| value(p))
+            .fold(f64::INFINITY, f64::min);
+        let max = finite
+            .iter()
+            .map(|p| value(p))
+            .fold(f64::NEG_INFINITY, f64::max);
+        if finite.is_empty() {
+            (0.0, 1.0)
+        } else if max > min {
+            (min, max)
+        } else {
+            (min - 0.5, max + 0.5)
+        }
+    };
+    let (bpp_min, bpp_max) = range(|p| p.bpp);
+    let (psnr_min, psnr_max) = range(|p| p.psnr);
+    let x = |bpp: f64| MARGIN + (bpp - bpp_min) / (bpp_max - bpp_min) * (PLOT_WIDTH - 2.0 * MARGIN);
+    let y = |psnr: f64| {
+        PLOT_HEIGHT
+            - MARGIN
+            - (psnr - psnr_min) / (psnr_max - psnr_min) * (PLOT_HEIGHT - 2.0 * MARGIN)
+    };
+
+    let mut svg = format!(
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{0}\" height=\"{1}\" viewBox=\"0 0 {0} {1}\" font-family=\"sans-serif\" font-size=\"12\">\n",
+        PLOT_WIDTH, PLOT_HEIGHT
+    );
+    svg += "<rect width=\"100%\" height=\"100%\" fill=\"white\"/>\n";
+    svg += &format!(
+        "<text x=\"{}\" y=\"20\" text-anchor=\"middle\">{}</text>\n",
+        PLOT_WIDTH / 2.0,
+        escape(title)
+    );
+
+    /*axes with their ranges*/
+
+    svg += &format!(
+        "<path d=\"M {0} {1} V {2} H {3}\" fill=\"none\" stroke=\"black\"/>\n",
+        MARGIN,
+        MARGIN,
+        PLOT_HEIGHT - MARGIN,
+        PLOT_WIDTH - MARGIN
+    );
+    for &(bpp, anchor) in [(bpp_min, "start"), (bpp_max, "end")].iter() {
+        svg += &format!(
+            "<text x=\"{}\" y=\"{}\" text-anchor=\"{}\">{:.3}</text>\n",
+            x(bpp),
+            PLOT_HEIGHT - MARGIN + 16.0,
+            anchor,
+            bpp
+        );
+    }
+    for &psnr in [psnr_min, psnr_max].iter() {
+        svg += &format!(
+            "<text x=\"{}\" y=\"{}\" text-anchor=\"end\">{:.1}</text>\n",
+            MARGIN - 4.0,
+            y(psnr) + 4.0,
+            psnr
+        );
+    }
+    svg += &format!(
+        "<text x=\"{}\" y=\"{}\" text-anchor=\"middle\">bits per pixel</text>\n",
+        PLOT_WIDTH / 2.0,
+        PLOT_HEIGHT - 12.0
+    );
+    svg += &format!(
+        "<text x=\"14\" y=\"{0}\" text-anchor=\"middle\" transform=\"rotate(-90 14 {0})\">PSNR (dB)</text>\n",
+        PLOT_HEIGHT / 2.0
+    );
+
+    /*one line per codec, in the order they first appear*/
+
+    let mut codecs: Vec<&str> = Vec::new();
+    for point in &finite {
+        if !codecs.contains(&point.codec.as_str()) {
+            codecs.push(&point.codec);
+        }
+    }
+    for (i, codec) in codecs.iter().enumerate() {
+        let color = COLORS[i % COLORS.len()];
+        let line: Vec<String> = finite
+            .iter()
+            .filter(|p| p.codec == *codec)
+            .map(|p| format!("{:.1},{:.1}", x(p.bpp), y(p.psnr)))
+            .collect();
+        svg += &format!(
+            "<polyline points=\"{}\" fill=\"none\" stroke=\"{}\" stroke-width=\"2\"/>\n",
+            line.join(" "),
+            color
+        );
+        svg += &format!(
+            "<text x=\"{}\" y=\"{}\" fill=\"{}\">{}</text>\n",
+            MARGIN + 10.0,
+            MARGIN + 16.0 * (i + 1) as f64,
+            color,
+            escape(codec)
+        );
+    }
+    svg += "</svg>\n";
+    svg
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::codec::{Dct, Dwt};
+    use crate::image::tests::test_data;
+    use crate::image::{ColorTransform, Subsampling, Wavelet};
+
+    fn points() -> Vec<Point> {
+        let point = |codec: &str, coefficient, bits, psnr| Point {
+            codec: codec.to_string(),
+            coefficient,
+            bits,
+            bpp: bits / 64.0,
+            psnr,
+            ssim: 0.5,
+        };
+        vec![
+            point("dct", 16, 64.0, 20.0),
+            point("dwt_haar", 16, 48.0, 22.0),
+            point("dct", 64, 256.0, f64::INFINITY),
+        ]
+    }
+
+    #[test]
+    fn estimated_bits_of_coefficients() {
+        assert_eq!(estimated_bits(&[0.0; 8]), 0.0);
+        // every coefficient kept with the same value costs nothing
+        assert_eq!(estimated_bits(&[3.0; 4]), 0.0);
+        // two zeros with one bit each, two values with two bits each
+        assert!((estimated_bits(&[1.0, 0.0, -1.0, 0.0]) - 6.0).abs() < 1e-12);
+        assert!(estimated_bits(&[1.0, 2.0, 3.0, 4.0]) > estimated_bits(&[1.0, 1.0, 1.0, 2.0]));
+    }
+
+    #[test]
+    fn bits_grow_with_the_budget() {
+        // smooth gradients, most coefficients of which round to zero
+        let (width, height) = (64, 48);
+        let data: Vec<u8> = (0..3 * width * height)
+            .map(|i| (i % width * 2 + i / width % height * 3 + i / (width * height) * 20) as u8)
+            .collect();
+        let mut image = Image::new_from_rgb(width, height, 1, &data).unwrap();
+        image
+            .set_color(ColorTransform::Ict)
+            .set_subsampling(Subsampling::S420);
+        let mut dwt = Dwt::new(Wavelet::Cdf97, 5);
+        dwt.set_tile_size(Some((32, 32)));
+        let codecs: [&dyn Codec; 2] = [&Dct::default(), &dwt];
+        for codec in codecs.iter() {
+            let mut last = 0.0;
+            for coefficient in (0..=width * height).step_by(96) {
+                let (coefficients, _) = codec.reconstruct(&image, coefficient).unwrap();
+                let bits = coefficients.estimated_bits();
+                assert!(
+                    bits > last - 1e-6,
+                    "{} {}: {} < {}",
+                    codec.tag(),
+                    coefficient,
+                    bits,
+                    last
+                );
+                last = bits;
+            }
+        }
+    }
+
+    #[test]
+    fn budgets_grow_with_the_target() {
+        let (width, height) = (64, 48);
+        let mut image = Image::new_from_rgb(width, height, 1, &test_data(width, height)).unwrap();
+        image
+            .set_color(ColorTransform::Ict)
+            .set_subsampling(Subsampling::S420);
+        let mut dwt = Dwt::new(Wavelet::Cdf97, 5);
+        dwt.set_tile_size(Some((32, 32)));
+        let budgets: Vec<usize> = [1.95, 2.5, 2.9]
+            .iter()
+            .map(|&bpp| budget_for_bpp(&image, bpp, &dwt).unwrap())
+            .collect();
+        assert!(budgets[0] < budgets[1] && budgets[1] < budgets[2]);
+        assert!(budgets[2] < width * height);
+        for (budget, bpp) in budgets.iter().zip(&[1.95, 2.5, 2.9]) {
+            let (coefficients, _) = dwt.reconstruct(&image, *budget).unwrap();
+            assert!(coefficients.estimated_bits() <= bpp * (width * height) as f64);
+        }
+    }
+
+    #[test]
+    fn budgets_for_bpp() {
+        let (width, height) = (16, 16);
+        let image = Image::new_from_rgb(width, height, 64, &test_data(width, height)).unwrap();
+
+        let budget = budget_for_bpp(&image, 2.0, &Dct::default()).unwrap();
+        let (coefficients, _) = Dct::default().reconstruct(&image, budget).unwrap();
+        assert!(coefficients.estimated_bits() <= 2.0 * (width * height) as f64);
+        let (coefficients, _) = Dct::default().reconstruct(&image, budget + 1).unwrap();
+        assert!(coefficients.estimated_bits() > 2.0 * (width * height) as f64);
+        let point = Point::new("dct", &coefficients, 30.0, 0.9);
+        assert_eq!(
+            (point.coefficient, point.psnr, point.ssim),
+            (budget + 1, 30.0, 0.9)
+        );
+        assert_eq!(
+            point.bpp,
+            coefficients.estimated_bits() / (width * height) as f64
+        );
+
+        let dwt = Dwt::default();
+        assert_eq!(
+            budget_for_bpp(&image, 1000.0, &dwt).unwrap(),
+            width * height
+        );
+        assert_eq!(budget_for_bpp(&image, 1e-9, &dwt).unwrap(), 1);
+    }
+
+    #[test]
+    fn tables() {
+        let points = points();
+        assert_eq!(
+            to_csv(&points[..1]),
+            "codec,coefficient,bits,bpp,psnr,ssim\ndct,16,64,1.0000,20.0000,0.5000\n"
+        );
+        let json = to_json(&points);
+        assert!(json.starts_with("[\n  {\"codec\": \"dct\", \"coefficient\": 16, \"bits\": 64,"));
+        assert!(json.contains("\"psnr\": null"));
+        assert!(json.ends_with("}\n]\n"));
+        assert_eq!(Format::from_name("json"), Some(Format::Json));
+        assert_eq!(Format::from_name("xml"), None);
+    }
+
+    #[test]
+    fn svg_plot() {
+        let svg = to_svg(&points(), "lena <rgb>");
+        assert_eq!(svg.matches("<polyline").count(), 2);
+        assert!(svg.contains("lena &lt;rgb&gt;"));
+        assert!(!svg.contains("NaN") && !svg.contains("inf"));
+        assert!(to_svg(&[], "empty").ends_with("</svg>\n"));
+    }
+}
@@ -28,7 +28,7 @@ impl Iterator for Zigzag {
             // coordination (j, i) width, height (x, y)
             let old = (self.j - 1, self.i - 1);
 
-            if (self.i + self.j) % 2 == 0 {
+            if (self.i + self.j).is_multiple_of(2) {
                 // right-up
                 if self.j < self.blocksize_x {
                     self.j += 1;
@@ -65,7 +65,7 @@ mod tests {
 
     #[test]
     fn test_zigzag() {
-        let mut z = Zigzag::new(16, 4, 4).into_iter();
+        let mut z = Zigzag::new(16, 4, 4);
 
         for i in z.by_ref().take(25) {
             println!("{:?}", i);
@@ -76,7 +76,7 @@ mod tests {
     fn test_zigzag_rec() {
         let blocksize_x = 5;
         let blocksize_y = 3;
-        let mut z = Zigzag::new(blocksize_x * blocksize_y, blocksize_x, blocksize_y).into_iter();
+        let mut z = Zigzag::new(blocksize_x * blocksize_y, blocksize_x, blocksize_y);
 
         for i in z.by_ref().take(blocksize_x * blocksize_y) {
             println!("{:?}", i);